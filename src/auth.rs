@@ -1,69 +1,179 @@
-use keyring::Keyring;
 use std::fmt;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+use tokio::sync::{Mutex, oneshot};
 
-use crate::browser::BrowserAutomation;
+use crate::browser::{LoopbackServer, open_browser};
 use crate::config::Config;
-use crate::error::{McpError, McpResult, to_credential_error};
-use crate::supabase::{SupabaseClient, UserProfile};
+use crate::credentials::{CredentialStore, open_store, read_only_error};
+use crate::device::GitHubDeviceFlow;
+use crate::error::{McpError, McpResult};
+use crate::jwt::{SignatureCheck, TokenValidator};
+use crate::pkce::PkceChallenge;
+use crate::provider::{OAuthProvider, display_name};
+use crate::session::{Session, now_secs};
+use crate::supabase::{LogoutScope, SupabaseClient, UserProfile, generate_state};
 
-const SERVICE_NAME: &str = "rust-mcp";
-const USERNAME: &str = "supabase-token";
+/// Refresh the session when the access token expires within this window
+const REFRESH_LEEWAY: Duration = Duration::from_secs(60);
 
 /// Authentication handler for the CLI
 pub struct AuthHandler {
     config: Config,
     supabase: SupabaseClient,
+    store: Box<dyn CredentialStore>,
+    
+    /// Serializes refreshes so concurrent callers do not spend the same refresh token twice
+    refresh_lock: Mutex<()>,
 }
 
 impl AuthHandler {
     /// Create a new authentication handler
     pub fn new(config: Config) -> Self {
+        let store = open_store(config.credential_store);
+        Self::with_store(config, store)
+    }
+    
+    /// Create a handler that keeps sessions in `store`
+    pub(crate) fn with_store(config: Config, store: Box<dyn CredentialStore>) -> Self {
         let supabase = SupabaseClient::new(config.clone());
-        Self { config, supabase }
+        Self {
+            config,
+            supabase,
+            store,
+            refresh_lock: Mutex::new(()),
+        }
     }
     
-    /// Sign up a new user through GitHub OAuth
-    pub async fn signup(&self) -> McpResult<()> {
-        println!("Initiating signup process with GitHub...");
+    /// Sign up a new user through an OAuth provider
+    pub async fn signup(&self, provider: &OAuthProvider) -> McpResult<()> {
+        self.check_store_writable()?;
+        println!("Initiating signup process with {}...", provider.display_name());
         
         // Perform the OAuth flow
-        let token = self.perform_oauth_flow(true).await?;
+        let session = self.perform_oauth_flow(provider, true).await?;
         
-        // Store the token
-        self.store_token(&token)?;
+        self.complete_sign_in(&session, "Signup successful!").await
+    }
+    
+    /// Log in an existing user through an OAuth provider
+    pub async fn login(&self, provider: &OAuthProvider) -> McpResult<()> {
+        self.check_store_writable()?;
+        println!("Initiating login process with {}...", provider.display_name());
         
-        // Get user profile to confirm it worked
-        let user = self.supabase.get_user_profile(&token).await?;
+        // Perform the OAuth flow
+        let session = self.perform_oauth_flow(provider, false).await?;
         
-        println!("Signup successful!");
-        self.print_user_info(&user);
+        self.complete_sign_in(&session, "Login successful!").await
+    }
+    
+    /// Log in through GitHub's device flow, entering a code on any other device
+    ///
+    /// Needs no local browser or loopback port, so it works over SSH and in containers.
+    pub async fn login_with_device(&self) -> McpResult<()> {
+        self.check_store_writable()?;
+        let grant_type = self.device_grant_type()?;
         
-        Ok(())
+        println!("Initiating device login with GitHub...");
+        
+        let flow = GitHubDeviceFlow::new(&self.config);
+        let authorization = flow.start().await?;
+        
+        println!("Open {} and enter the code: {}", authorization.verification_uri, authorization.user_code);
+        println!("Waiting for authorization (the code expires in {} minutes)...", authorization.expires_in / 60);
+        
+        let github_token = flow.wait_for_token(&authorization).await?;
+        
+        // Trade the GitHub identity for a Supabase session
+        let session = self.supabase.exchange_provider_token(grant_type, "github", &github_token).await?;
+        
+        self.complete_sign_in(&session, "Login successful!").await
+    }
+    
+    /// The grant that turns a GitHub token into a session, checked before the user is asked
+    /// for a code that could not be used
+    fn device_grant_type(&self) -> McpResult<&str> {
+        // `id_token` needs an OpenID Connect ID token, which GitHub does not issue, and Supabase
+        // Auth has no grant that accepts a GitHub access token
+        match self.config.device_grant_type.as_deref() {
+            Some(grant_type) if grant_type != "id_token" => Ok(grant_type),
+            _ => Err(McpError::ConfigError(
+                "Device login needs device_grant_type: a token grant, added to the auth server, \
+                 that accepts GitHub access tokens. Supabase Auth has none. Use `login --email` \
+                 on machines without a browser instead."
+                    .to_string(),
+            )),
+        }
     }
     
-    /// Log in an existing user through GitHub OAuth
-    pub async fn login(&self) -> McpResult<()> {
-        println!("Initiating login process with GitHub...");
+    /// Sign up a new user with an email address and password
+    pub async fn signup_with_password(&self, email: &str, password: &str) -> McpResult<()> {
+        self.check_store_writable()?;
+        println!("Signing up {}...", email);
         
-        // Perform the OAuth flow
-        let token = self.perform_oauth_flow(false).await?;
+        match self.supabase.sign_up_with_password(email, password).await? {
+            Some(session) => self.complete_sign_in(&session, "Signup successful!").await,
+            None => {
+                println!("Signup successful! Confirm the address with the link sent to {}, then run:", email);
+                println!("  rust-mcp login --email {} --password", email);
+                Ok(())
+            }
+        }
+    }
+    
+    /// Log in with an email address and password
+    pub async fn login_with_password(&self, email: &str, password: &str) -> McpResult<()> {
+        self.check_store_writable()?;
+        println!("Logging in {}...", email);
         
-        // Store the token
-        self.store_token(&token)?;
+        let session = self.supabase.sign_in_with_password(email, password).await?;
         
-        // Get user profile to confirm it worked
-        let user = self.supabase.get_user_profile(&token).await?;
+        self.complete_sign_in(&session, "Login successful!").await
+    }
+    
+    /// Log in with a magic link or one-time code emailed to `email`
+    ///
+    /// Opening the link on this machine completes the login through the loopback server;
+    /// on any other device, the code from the same email can be typed in instead.
+    pub async fn login_with_email(&self, email: &str) -> McpResult<()> {
+        self.check_store_writable()?;
+        let server = LoopbackServer::bind().await?;
+        let pkce = PkceChallenge::generate();
+        let state = generate_state();
         
-        println!("Login successful!");
-        self.print_user_info(&user);
+        println!("Sending a sign-in email to {}...", email);
+        self.supabase.send_email_otp(
+            email,
+            &server.redirect_uri(),
+            &pkce.challenge,
+            &state,
+        ).await?;
         
-        Ok(())
+        println!("Open the link in the email on this machine, or enter the code it contains.");
+        print!("Code: ");
+        std::io::stdout().flush()?;
+        
+        let session = tokio::select! {
+            callback = server.wait_for_callback() => {
+                let callback = callback?;
+                callback.verify_state(&state)?;
+                let code = callback.into_code()?;
+                
+                println!();
+                self.supabase.exchange_code_for_session(&code, &pkce.verifier).await?
+            }
+            code = read_code() => {
+                self.supabase.verify_email_otp(email, &code?).await?
+            }
+        };
+        
+        self.complete_sign_in(&session, "Login successful!").await
     }
     
     /// Get the current user profile using the stored token
     pub async fn whoami(&self) -> McpResult<()> {
-        // Get the stored token
-        let token = self.get_token()?;
+        // Get the stored token, refreshing it if needed
+        let token = self.get_token().await?;
         
         // Get the user profile
         let user = self.supabase.get_user_profile(&token).await?;
@@ -74,49 +184,329 @@ impl AuthHandler {
         Ok(())
     }
     
-    /// Perform the OAuth flow and return the token
-    async fn perform_oauth_flow(&self, is_signup: bool) -> McpResult<String> {
-        // Create browser automation
-        let browser = BrowserAutomation::new()?;
+    /// Decode the stored access token and print its claims
+    pub async fn inspect_token(&self, verify: bool, json: bool) -> McpResult<()> {
+        let token = self.get_token().await?;
+        let validator = TokenValidator::new(self.config.clone());
         
-        // Check if already logged in to GitHub
-        let is_logged_in = browser.is_github_logged_in().await?;
-        if is_logged_in {
-            println!("User is already logged in to GitHub. Using existing session.");
+        let (claims, signature) = if verify {
+            let (claims, check) = validator.verify(&token).await?;
+            (claims, Some(check))
         } else {
-            println!("GitHub login required. Please log in using the browser window.");
+            (validator.decode(&token)?, None)
+        };
+        
+        if json {
+            println!("{}", serde_json::to_string_pretty(&claims)?);
+            return Ok(());
+        }
+        
+        println!("Subject: {}", claims.sub);
+        if let Some(email) = &claims.email {
+            println!("Email: {}", email);
+        }
+        if let Some(role) = &claims.role {
+            println!("Role: {}", role);
+        }
+        if let Some(aal) = &claims.aal {
+            println!("Assurance level: {}", aal);
+        }
+        if let Some(session_id) = &claims.session_id {
+            println!("Session ID: {}", session_id);
         }
+        if let Some(iss) = &claims.iss {
+            println!("Issuer: {}", iss);
+        }
+        if let Some(aud) = &claims.aud {
+            println!("Audience: {}", aud);
+        }
+        if let Some(iat) = claims.iat {
+            println!("Issued at: {}", iat);
+        }
+        println!(
+            "Expires at: {} (in {}s)",
+            claims.exp,
+            claims.exp.saturating_sub(now_secs())
+        );
+        println!("App metadata: {}", claims.app_metadata);
+        
+        match signature {
+            Some(SignatureCheck::Jwks) => println!("Signature: verified with the project JWKS"),
+            Some(SignatureCheck::Secret) => println!("Signature: verified with the JWT secret"),
+            None => println!("Signature: not verified (use --verify)"),
+        }
+        
+        Ok(())
+    }
+    
+    /// List the sign-in identities linked to the account
+    pub async fn list_identities(&self) -> McpResult<()> {
+        let token = self.get_token().await?;
+        
+        for identity in self.supabase.list_identities(&token).await? {
+            let login = identity.email.as_deref()
+                .or_else(|| identity.identity_data["user_name"].as_str())
+                .or_else(|| identity.identity_data["preferred_username"].as_str())
+                .unwrap_or("-");
+            
+            println!(
+                "{}\t{}\t{}\t{}",
+                identity.identity_id,
+                display_name(&identity.provider),
+                login,
+                identity.last_sign_in_at.as_deref().unwrap_or("-")
+            );
+        }
+        
+        Ok(())
+    }
+    
+    /// Link an identity from another provider to the account, signing in with it in the browser
+    pub async fn link_identity(&self, provider: &OAuthProvider) -> McpResult<()> {
+        println!("Linking a {} identity...", provider.display_name());
+        self.check_provider_enabled(provider).await?;
+        
+        let token = self.get_token().await?;
+        let server = LoopbackServer::bind().await?;
+        let pkce = PkceChallenge::generate();
+        let state = generate_state();
+        
+        let auth_url = self.supabase.build_link_identity_url(
+            &token,
+            provider,
+            &server.redirect_uri(),
+            &pkce.challenge,
+            &state,
+        ).await?;
+        
+        // The new session's token lists the linked provider
+        let session = self.authorize_in_browser(&auth_url, &server, &state, &pkce).await?;
+        
+        self.complete_sign_in(&session, "Identity linked!").await
+    }
+    
+    /// Unlink an identity, refusing to remove the last one the account can sign in with
+    pub async fn unlink_identity(&self, identity_id: &str) -> McpResult<()> {
+        let token = self.get_token().await?;
+        let identities = self.supabase.list_identities(&token).await?;
+        
+        let identity = identities
+            .iter()
+            .find(|identity| identity.identity_id == identity_id)
+            .ok_or_else(|| McpError::InvalidArguments(format!(
+                "No identity {} on this account; see `rust-mcp identity list`",
+                identity_id
+            )))?;
+        
+        if identities.len() == 1 {
+            return Err(McpError::InvalidArguments(format!(
+                "{} is the account's only identity; link another one before unlinking it",
+                display_name(&identity.provider)
+            )));
+        }
+        
+        self.supabase.unlink_identity(&token, identity_id).await?;
+        println!("Unlinked the {} identity {}.", display_name(&identity.provider), identity_id);
+        
+        // The stored access token lists the provider until the session is refreshed
+        if let Err(err) = self.session_with(|_| true).await {
+            println!("Warning: could not refresh the session: {}", err);
+        }
+        
+        Ok(())
+    }
+    
+    /// Log out: revoke the session server-side and remove the local credentials
+    ///
+    /// Local credentials are removed even when the server cannot be reached.
+    pub async fn logout(&self, scope: LogoutScope) -> McpResult<()> {
+        let revoked = match self.get_token().await {
+            Ok(token) => self.supabase.logout(&token, scope).await,
+            Err(McpError::NotAuthenticated) => {
+                println!("Not logged in.");
+                return Ok(());
+            }
+            Err(err) => Err(err),
+        };
+        
+        match revoked {
+            Ok(()) => println!("Session revoked on the server (scope: {}).", scope.as_str()),
+            Err(err) => println!("Warning: could not revoke the session on the server: {}", err),
+        }
+        
+        // The current session stays signed in when only the others are revoked
+        if matches!(scope, LogoutScope::Others) {
+            return Ok(());
+        }
+        
+        TokenValidator::new(self.config.clone()).clear_cache();
+        
+        if self.store.is_read_only() {
+            println!("Local credentials come from the {} store and were left in place.", self.store.name());
+            return Ok(());
+        }
+        
+        self.delete_session()?;
+        println!("Local credentials removed from the {} store.", self.store.name());
+        
+        Ok(())
+    }
+    
+    /// Perform the OAuth flow and return the new session
+    async fn perform_oauth_flow(&self, provider: &OAuthProvider, is_signup: bool) -> McpResult<Session> {
+        self.check_provider_enabled(provider).await?;
+        
+        // Listen for the redirect on an ephemeral loopback port
+        let server = LoopbackServer::bind().await?;
+        let pkce = PkceChallenge::generate();
+        
+        // Keep the state so the callback can be tied back to this attempt
+        let state = generate_state();
         
         // Build the auth URL
-        let auth_url = self.supabase.build_github_auth_url(is_signup)?;
+        let auth_url = self.supabase.build_auth_url(
+            provider,
+            is_signup,
+            &server.redirect_uri(),
+            &pkce.challenge,
+            &state,
+        ).await?;
+        
+        self.authorize_in_browser(&auth_url, &server, &state, &pkce).await
+    }
+    
+    /// Send the user to `auth_url` and exchange the code the loopback server receives
+    async fn authorize_in_browser(
+        &self,
+        auth_url: &str,
+        server: &LoopbackServer,
+        state: &str,
+        pkce: &PkceChallenge,
+    ) -> McpResult<Session> {
+        // Open the user's browser and wait for the authorization code
+        println!("Opening browser to authenticate with {}...", self.config.supabase_url);
+        if let Err(err) = open_browser(auth_url) {
+            eprintln!("Could not open browser automatically: {}", err);
+        }
         
-        // Get the callback URL prefix for checking success
-        let callback_url_prefix = self.supabase.get_callback_url_prefix();
+        // Reject callbacks that were not started by this flow before looking at the code
+        let callback = server.wait_for_callback().await?;
+        callback.verify_state(state)?;
+        let code = callback.into_code()?;
         
-        // Open browser and wait for authentication
-        println!("Opening browser for authentication...");
-        let final_url = browser.authenticate_with_github(&auth_url, &callback_url_prefix).await?;
+        // Exchange the code for a session
+        self.supabase.exchange_code_for_session(&code, &pkce.verifier).await
+    }
+    
+    /// Fail early for a provider the project has not enabled, which would leave the browser
+    /// on an error page; if the settings cannot be read, the flow is attempted anyway
+    async fn check_provider_enabled(&self, provider: &OAuthProvider) -> McpResult<()> {
+        let Ok(enabled) = self.supabase.enabled_providers().await else {
+            return Ok(());
+        };
         
-        // Extract the token from the URL
-        let token = browser.extract_token_from_url(&final_url)?;
+        if enabled.contains(&provider.name) {
+            return Ok(());
+        }
         
-        Ok(token)
+        let mut enabled: Vec<&str> = enabled.iter().map(String::as_str).filter(|name| *name != "email").collect();
+        enabled.sort_unstable();
+        Err(McpError::AuthError(format!(
+            "{} sign-in is not enabled on this project (enabled providers: {})",
+            provider.display_name(),
+            if enabled.is_empty() { "none".to_string() } else { enabled.join(", ") }
+        )))
     }
     
-    /// Store the authentication token securely
-    fn store_token(&self, token: &str) -> McpResult<()> {
-        let keyring = Keyring::new(SERVICE_NAME, USERNAME);
-        keyring.set_password(token).map_err(to_credential_error)?;
+    /// Fail before a login starts if its session could not be saved, as when the token comes
+    /// from the environment
+    fn check_store_writable(&self) -> McpResult<()> {
+        if self.store.is_read_only() {
+            return Err(read_only_error());
+        }
         
         Ok(())
     }
     
-    /// Get the stored authentication token
-    fn get_token(&self) -> McpResult<String> {
-        let keyring = Keyring::new(SERVICE_NAME, USERNAME);
-        let token = keyring.get_password().map_err(|_| McpError::NotAuthenticated)?;
+    /// Store a new session and confirm it works by printing its user
+    async fn complete_sign_in(&self, session: &Session, message: &str) -> McpResult<()> {
+        self.store_session(session)?;
+        
+        // Get user profile to confirm it worked
+        let user = self.supabase.get_user_profile(&session.access_token).await?;
+        
+        println!("{}", message);
+        self.print_user_info(&user);
         
-        Ok(token)
+        Ok(())
+    }
+    
+    /// Store the session securely
+    fn store_session(&self, session: &Session) -> McpResult<()> {
+        self.store.save(&self.config.profile, session)
+    }
+    
+    /// Load the stored session
+    fn load_session(&self) -> McpResult<Session> {
+        self.store
+            .load(&self.config.profile)?
+            .ok_or(McpError::NotAuthenticated)
+    }
+    
+    /// Delete the stored session
+    pub fn delete_session(&self) -> McpResult<()> {
+        self.store.delete(&self.config.profile)
+    }
+    
+    /// Get a valid access token, refreshing the stored session if it is about to expire
+    pub async fn get_token(&self) -> McpResult<String> {
+        Ok(self.get_session().await?.access_token)
+    }
+    
+    /// Get the stored session, refreshing it first if the access token is about to expire
+    pub async fn get_session(&self) -> McpResult<Session> {
+        self.session_with(|session| session.expires_within(REFRESH_LEEWAY)).await
+    }
+    
+    /// Get a new access token after a server rejected `rejected`
+    ///
+    /// If another caller already replaced the rejected token, its replacement is returned
+    /// instead of refreshing again.
+    pub async fn refresh_rejected_token(&self, rejected: &str) -> McpResult<String> {
+        let session = self
+            .session_with(|session| session.access_token == rejected)
+            .await?;
+        
+        Ok(session.access_token)
+    }
+    
+    /// Load the stored session and refresh it if `needs_refresh` says so
+    async fn session_with(&self, needs_refresh: impl Fn(&Session) -> bool) -> McpResult<Session> {
+        let _guard = self.refresh_lock.lock().await;
+        let mut session = self.load_session()?;
+        
+        if needs_refresh(&session) {
+            if session.refresh_token.is_empty() {
+                return Err(McpError::SessionExpired);
+            }
+            
+            session = self.supabase.refresh_session(&session.refresh_token).await?;
+            
+            // The old refresh token is now spent, so persist the rotated one immediately
+            self.store_session(&session)?;
+        }
+        
+        Ok(session)
+    }
+    
+    /// The profile settings this handler was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+    
+    /// The Supabase client for the profile's project
+    pub fn supabase(&self) -> &SupabaseClient {
+        &self.supabase
     }
     
     /// Print user information in a formatted way
@@ -133,17 +523,57 @@ impl AuthHandler {
             println!("Name: {}", full_name);
         }
         
+        let provider = display_name(&user.app_metadata.provider);
+        
         if let Some(username) = &user.user_metadata.preferred_username {
-            println!("GitHub Username: {}", username);
+            println!("{} Username: {}", provider, username);
         } else if let Some(username) = &user.user_metadata.user_name {
-            println!("GitHub Username: {}", username);
+            println!("{} Username: {}", provider, username);
         }
         
-        println!("Provider: {}", user.app_metadata.provider);
+        println!("Provider: {}", provider);
+        
+        if user.app_metadata.providers.len() > 1 {
+            let linked: Vec<String> = user.app_metadata.providers.iter().map(|name| display_name(name)).collect();
+            println!("Linked providers: {}", linked.join(", "));
+        }
         println!("Account created at: {}", user.created_at);
     }
 }
 
+/// Read a one-time code from stdin
+///
+/// Never finishes if stdin closes first, so the login can still complete through the link.
+async fn read_code() -> McpResult<String> {
+    // A blocking read cannot be cancelled, and one on the runtime's blocking pool would keep
+    // the process alive after the link wins until Enter is pressed; a detached thread does not
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(read_code_line());
+    });
+    
+    match rx.await {
+        Ok(Ok(Some(code))) => Ok(code),
+        Ok(Err(err)) => Err(err),
+        Ok(Ok(None)) | Err(_) => std::future::pending().await,
+    }
+}
+
+/// Read lines from stdin until one is not blank; `None` at end of input
+fn read_code_line() -> McpResult<Option<String>> {
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        let code = line.trim();
+        if !code.is_empty() {
+            return Ok(Some(code.to_string()));
+        }
+        print!("Code: ");
+        std::io::stdout().flush()?;
+    }
+    
+    Ok(None)
+}
+
 impl fmt::Display for UserProfile {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "User(id={}", self.id)?;
@@ -167,3 +597,187 @@ impl fmt::Display for UserProfile {
         write!(f, ")")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::{CredentialStoreKind, MemoryStore};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    
+    /// Serve an auth API that rotates refresh tokens and accepts logouts
+    ///
+    /// Returns the project URL and the requests made to it, each as its path and body;
+    /// metadata discovery misses are left out.
+    async fn auth_server() -> (String, Arc<StdMutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(StdMutex::new(Vec::new()));
+        
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut socket = BufReader::new(socket);
+                
+                let mut request_line = String::new();
+                socket.read_line(&mut request_line).await.unwrap();
+                let path = request_line.split(' ').nth(1).unwrap().to_string();
+                
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    socket.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                socket.read_exact(&mut body).await.unwrap();
+                
+                let (status, body_out) = if path.contains("/.well-known/") {
+                    ("404 Not Found", String::new())
+                } else if path == "/auth/v1/token?grant_type=refresh_token" {
+                    let session = json!({
+                        "access_token": "fresh",
+                        "refresh_token": "rotated",
+                        "token_type": "bearer",
+                        "expires_in": 3600,
+                    });
+                    ("200 OK", session.to_string())
+                } else if path.starts_with("/auth/v1/logout") {
+                    ("204 No Content", String::new())
+                } else {
+                    ("404 Not Found", String::new())
+                };
+                
+                if !path.contains("/.well-known/") {
+                    let request = format!("{} {}", path, String::from_utf8(body).unwrap());
+                    seen.lock().unwrap().push(request.trim_end().to_string());
+                }
+                
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body_out.len(),
+                    body_out
+                );
+                socket.get_mut().write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        
+        (url, requests)
+    }
+    
+    /// A handler for the project at `url` whose memory store holds `session`
+    fn signed_in(url: &str, session: Session) -> AuthHandler {
+        // A profile of its own, so logging out clears no real JWKS cache
+        let config = Config {
+            supabase_url: url.to_string(),
+            profile: "auth-tests".to_string(),
+            ..Config::default()
+        };
+        let store = MemoryStore::default();
+        store.save(&config.profile, &session).unwrap();
+        AuthHandler::with_store(config, Box::new(store))
+    }
+    
+    #[tokio::test]
+    async fn expiring_sessions_are_refreshed_and_the_rotated_token_kept() {
+        let (url, requests) = auth_server().await;
+        let auth = signed_in(&url, Session {
+            access_token: "stale".to_string(),
+            refresh_token: "spent".to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: 3600,
+            expires_at: now_secs() + REFRESH_LEEWAY.as_secs() / 2,
+        });
+        
+        assert_eq!(auth.get_token().await.unwrap(), "fresh");
+        assert_eq!(auth.get_token().await.unwrap(), "fresh");
+        
+        let stored = auth.load_session().unwrap();
+        assert_eq!(stored.refresh_token, "rotated");
+        assert!(!stored.expires_within(REFRESH_LEEWAY));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [r#"/auth/v1/token?grant_type=refresh_token {"refresh_token":"spent"}"#]
+        );
+    }
+    
+    #[tokio::test]
+    async fn a_rejected_token_is_refreshed_once_for_concurrent_callers() {
+        let (url, requests) = auth_server().await;
+        let auth = signed_in(&url, Session {
+            access_token: "revoked".to_string(),
+            refresh_token: "spent".to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: 3600,
+            expires_at: now_secs() + 3600,
+        });
+        
+        let (first, second) = tokio::join!(
+            auth.refresh_rejected_token("revoked"),
+            auth.refresh_rejected_token("revoked"),
+        );
+        assert_eq!(first.unwrap(), "fresh");
+        assert_eq!(second.unwrap(), "fresh");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+    
+    #[tokio::test]
+    async fn logout_forgets_the_session_unless_only_others_were_revoked() {
+        let (url, requests) = auth_server().await;
+        let auth = signed_in(&url, Session {
+            access_token: "current".to_string(),
+            refresh_token: "refresh".to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: 3600,
+            expires_at: now_secs() + 3600,
+        });
+        
+        auth.logout(LogoutScope::Others).await.unwrap();
+        assert_eq!(auth.get_token().await.unwrap(), "current");
+        
+        auth.logout(LogoutScope::Local).await.unwrap();
+        assert!(matches!(auth.get_token().await, Err(McpError::NotAuthenticated)));
+        
+        // With nothing stored there is nothing to revoke
+        auth.logout(LogoutScope::Global).await.unwrap();
+        assert_eq!(
+            *requests.lock().unwrap(),
+            ["/auth/v1/logout?scope=others", "/auth/v1/logout?scope=local"]
+        );
+    }
+    
+    #[tokio::test]
+    async fn read_only_credentials_refuse_login_and_are_never_refreshed() {
+        let (url, requests) = auth_server().await;
+        
+        // The environment store fails before any request, whether or not a token is set
+        let env = AuthHandler::new(Config {
+            supabase_url: url.clone(),
+            credential_store: CredentialStoreKind::Env,
+            ..Config::default()
+        });
+        let err = env.login_with_password("user@example.com", "secret").await.unwrap_err();
+        assert!(matches!(err, McpError::CredentialError(_)), "{}", err);
+        
+        // A bare access token, as the environment store hands out, is used until rejected
+        let auth = signed_in(&url, Session::from_access_token("from-env".to_string()));
+        assert_eq!(auth.get_token().await.unwrap(), "from-env");
+        assert!(matches!(
+            auth.refresh_rejected_token("from-env").await,
+            Err(McpError::SessionExpired)
+        ));
+        
+        assert!(requests.lock().unwrap().is_empty());
+    }
+}
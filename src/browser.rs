@@ -1,124 +1,219 @@
-use headless_chrome::{Browser, LaunchOptions};
-use std::sync::Arc;
+use std::collections::HashMap;
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
 use url::Url;
 
 use crate::error::{McpError, McpResult, to_browser_error};
 
-/// Timeout for browser operations in seconds
-const BROWSER_TIMEOUT: u64 = 60;
+/// Timeout for the user to finish authenticating in the browser, in seconds
+const CALLBACK_TIMEOUT: u64 = 300;
 
-/// Handles browser automation for the OAuth flow
-pub struct BrowserAutomation {
-    browser: Arc<Browser>,
+/// Path the loopback server expects the authorization server to redirect to
+const CALLBACK_PATH: &str = "/callback";
+
+/// Maximum size of the HTTP request head we are willing to read
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+/// How long a connection may take to send its request head
+///
+/// Browsers open speculative connections that never send anything; they must not hold up the
+/// real callback.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause before accepting again after the listener reports an error
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+const SUCCESS_PAGE: &str = "<html><body><h2>Authentication complete</h2>\
+<p>You can close this window and return to the terminal.</p></body></html>";
+
+const FAILURE_PAGE: &str = "<html><body><h2>Authentication failed</h2>\
+<p>Return to the terminal for details.</p></body></html>";
+
+/// Query parameters received on the loopback callback
+#[derive(Debug)]
+pub struct CallbackParams {
+    params: HashMap<String, String>,
 }
 
-impl BrowserAutomation {
-    /// Create a new browser automation instance
-    pub fn new() -> McpResult<Self> {
-        let options = LaunchOptions {
-            headless: false, // Set to true for production, false for debugging
-            ..Default::default()
-        };
-        
-        let browser = Browser::new(options).map_err(to_browser_error)?;
-        
-        Ok(Self {
-            browser: Arc::new(browser),
-        })
+impl CallbackParams {
+    /// Get a query parameter by name
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
     }
-    
-    /// Open a URL in the browser and wait for a redirect to the success URL
-    /// Returns the final URL which should contain the authentication token
-    pub async fn authenticate_with_github(&self, auth_url: &str, success_url_prefix: &str) -> McpResult<String> {
-        // Create a new tab
-        let tab = self.browser
-            .new_tab()
-            .map_err(to_browser_error)?;
-        
-        // Navigate to the authentication URL
-        tab.navigate_to(auth_url)
-            .map_err(to_browser_error)?;
-        
-        // Wait for the page to load
-        tab.wait_until_navigated()
-            .map_err(to_browser_error)?;
-        
-        let start_time = std::time::Instant::now();
-        
-        // Poll the current URL until we get to the success URL or timeout
-        while start_time.elapsed().as_secs() < BROWSER_TIMEOUT {
-            // Get the current URL
-            let current_url = tab.get_url();
-            
-            // Check if we've reached the success URL
-            if current_url.starts_with(success_url_prefix) {
-                // Close the tab
-                tab.close(true).map_err(to_browser_error)?;
-                
-                return Ok(current_url);
-            }
-            
-            // Sleep for a short time before checking again
-            std::thread::sleep(Duration::from_millis(500));
+
+    /// Check that the callback carries the state value issued for this flow
+    pub fn verify_state(&self, expected: &str) -> McpResult<()> {
+        match self.get("state") {
+            Some(state) if state == expected => Ok(()),
+            _ => Err(McpError::StateMismatch),
         }
-        
-        // If we got here, we timed out
-        Err(McpError::BrowserError("Authentication timed out".to_string()))
     }
-    
-    /// Extract the access token from a URL or page content
-    pub fn extract_token_from_url(&self, url: &str) -> McpResult<String> {
-        // Parse the URL
-        let parsed_url = Url::parse(url).map_err(to_browser_error)?;
-        
-        // First try to get the token from the hash fragment
-        if let Some(fragment) = parsed_url.fragment() {
-            // The fragment might be something like "access_token=xyz&token_type=bearer"
-            let params: Vec<&str> = fragment.split('&').collect();
-            
-            for param in params {
-                let kv: Vec<&str> = param.split('=').collect();
-                if kv.len() == 2 && kv[0] == "access_token" {
-                    return Ok(kv[1].to_string());
+
+    /// Return the authorization code, or the error reported by the authorization server
+    pub fn into_code(self) -> McpResult<String> {
+        if let Some(error) = self.get("error") {
+            let description = self.get("error_description").unwrap_or(error);
+            return Err(McpError::AuthError(format!(
+                "Authorization failed: {}",
+                description
+            )));
+        }
+
+        self.get("code")
+            .map(str::to_string)
+            .ok_or_else(|| McpError::AuthError("Callback did not include an authorization code".to_string()))
+    }
+}
+
+/// A one-shot HTTP server on an ephemeral loopback port that receives the OAuth redirect
+pub struct LoopbackServer {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl LoopbackServer {
+    /// Bind to an ephemeral port on 127.0.0.1
+    pub async fn bind() -> McpResult<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .map_err(to_browser_error)?;
+        let port = listener.local_addr().map_err(to_browser_error)?.port();
+
+        Ok(Self { listener, port })
+    }
+
+    /// The redirect URI the authorization server should send the browser back to
+    pub fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, CALLBACK_PATH)
+    }
+
+    /// Wait for the browser to hit the callback path and return its query parameters
+    pub async fn wait_for_callback(&self) -> McpResult<CallbackParams> {
+        let timeout = Duration::from_secs(CALLBACK_TIMEOUT);
+
+        tokio::time::timeout(timeout, self.accept_callback())
+            .await
+            .map_err(|_| McpError::BrowserError("Authentication timed out".to_string()))?
+    }
+
+    async fn accept_callback(&self) -> McpResult<CallbackParams> {
+        loop {
+            let mut stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    eprintln!("Could not accept a connection on the callback port: {}", err);
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
+            let request = read_callback(&mut stream);
+            let Ok(request) = tokio::time::timeout(REQUEST_READ_TIMEOUT, request).await else {
+                continue;
+            };
+
+            // One bad connection, such as a reset or a garbled request, must not end the login
+            let params = match request {
+                Ok(Some(params)) => params,
+                // Browsers also ask for things like /favicon.ico; ignore anything but the callback
+                Ok(None) => {
+                    respond(&mut stream, "404 Not Found", "").await;
+                    continue;
                 }
-            }
+                Err(err) => {
+                    eprintln!("Ignoring a bad request on the callback port: {}", err);
+                    respond(&mut stream, "400 Bad Request", "").await;
+                    continue;
+                }
+            };
+
+            let page = if params.get("error").is_some() {
+                FAILURE_PAGE
+            } else {
+                SUCCESS_PAGE
+            };
+            respond(&mut stream, "200 OK", page).await;
+
+            return Ok(params);
         }
-        
-        // If not found in fragment, try the query parameters
-        for (key, value) in parsed_url.query_pairs() {
-            if key == "access_token" {
-                return Ok(value.to_string());
-            }
+    }
+}
+
+/// Read a single HTTP request and parse its query if it targets the callback path
+async fn read_callback(stream: &mut TcpStream) -> McpResult<Option<CallbackParams>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.map_err(to_browser_error)?;
+        if n == 0 || buf.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
         }
-        
-        // If we couldn't find the token
-        Err(McpError::AuthError("Could not extract access token from URL".to_string()))
+        buf.extend_from_slice(&chunk[..n]);
     }
-    
-    /// Check if user is already logged in to GitHub by opening a test page
-    pub async fn is_github_logged_in(&self) -> McpResult<bool> {
-        // Create a new tab
-        let tab = self.browser
-            .new_tab()
-            .map_err(to_browser_error)?;
-        
-        // Navigate to GitHub
-        tab.navigate_to("https://github.com")
-            .map_err(to_browser_error)?;
-        
-        // Wait for the page to load
-        tab.wait_until_navigated()
-            .map_err(to_browser_error)?;
-        
-        // Check for elements that would indicate a logged-in state
-        // This is a simple heuristic and might need adjustment
-        let logged_in = tab.find_element("summary.Header-link[aria-label='View profile and more']")
-            .is_ok();
-        
-        // Close the tab
-        tab.close(true).map_err(to_browser_error)?;
-        
-        Ok(logged_in)
+
+    let head = String::from_utf8_lossy(&buf);
+    let target = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or_default();
+
+    let url = Url::parse(&format!("http://127.0.0.1{}", target)).map_err(to_browser_error)?;
+    if url.path() != CALLBACK_PATH {
+        return Ok(None);
+    }
+
+    let params = url.query_pairs().into_owned().collect();
+    Ok(Some(CallbackParams { params }))
+}
+
+/// Write a minimal HTTP response; failures are ignored since the browser may already be gone
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Open a URL in the user's default browser, printing it as a fallback
+pub fn open_browser(url: &str) -> McpResult<()> {
+    println!("If the browser does not open, visit this URL to continue:");
+    println!("{}", url);
+
+    open::that(url).map_err(to_browser_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bad_connections_do_not_end_the_wait() {
+        let server = LoopbackServer::bind().await.unwrap();
+        let addr = ("127.0.0.1", server.port);
+
+        let waiting = tokio::spawn(async move { server.wait_for_callback().await });
+
+        // A garbled request line and a stray request
+        let mut garbled = TcpStream::connect(addr).await.unwrap();
+        garbled.write_all(b"GET \xff@@ HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut favicon = TcpStream::connect(addr).await.unwrap();
+        favicon.write_all(b"GET /favicon.ico HTTP/1.1\r\n\r\n").await.unwrap();
+
+        let mut callback = TcpStream::connect(addr).await.unwrap();
+        callback
+            .write_all(b"GET /callback?code=abc&state=xyz HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+
+        let params = waiting.await.unwrap().unwrap();
+        params.verify_state("xyz").unwrap();
+        assert_eq!(params.into_code().unwrap(), "abc");
     }
 }
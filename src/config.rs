@@ -1,23 +1,94 @@
-use std::fs::{self, create_dir_all, File};
-use std::io::{Read, Write};
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, File};
+use std::io::Read;
 use std::path::PathBuf;
 
 use dirs::config_dir;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
+use crate::credentials::{CredentialStoreKind, write_private};
 use crate::error::{McpError, McpResult, to_config_error};
 
 const APP_NAME: &str = "rust-mcp";
 const CONFIG_FILE: &str = "config.json";
 
-#[derive(Debug, Serialize, Deserialize)]
+/// GitHub, unless a profile points at a GitHub Enterprise Server
+const DEFAULT_GITHUB_URL: &str = "https://github.com";
+
+/// Name of the profile used when none is selected
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Settings for a single profile: one Supabase project and one stored credential
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Name of the profile these settings belong to
+    #[serde(skip)]
+    pub profile: String,
+    
     // Supabase configuration
     pub supabase_url: String,
     pub supabase_anon_key: String,
     
     // GitHub OAuth app configuration
     pub github_client_id: String,
+    
+    /// Base URL of GitHub for the device flow, for GitHub Enterprise Server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_url: Option<String>,
+    
+    /// Token endpoint grant type that turns a device-flow GitHub token into a session
+    ///
+    /// Supabase Auth has no such grant, so device login only works with an auth server that
+    /// adds one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_grant_type: Option<String>,
+    
+    /// Where the session is stored
+    #[serde(default)]
+    pub credential_store: CredentialStoreKind,
+    
+    /// Project JWT secret, used to verify HS256-signed access tokens locally
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt_secret: Option<String>,
+    
+    /// Edge Functions the MCP server offers as tools
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<FunctionTool>,
+    
+    /// Sign-in settings per OAuth provider, keyed by provider name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub oauth_providers: BTreeMap<String, ProviderSettings>,
+}
+
+/// How to sign in with one OAuth provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderSettings {
+    /// Space-separated scopes, replacing the provider's defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<String>,
+    
+    /// Extra parameters for the provider's authorization page, e.g. `prompt=consent`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query_params: BTreeMap<String, String>,
+}
+
+/// An Edge Function exposed as an MCP tool, which sends the tool arguments as the JSON body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionTool {
+    /// Name of the Edge Function, also used as the tool name
+    pub name: String,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    
+    /// JSON Schema of the request body, advertised as the tool's input schema
+    #[serde(default = "any_object_schema")]
+    pub input_schema: Value,
+}
+
+fn any_object_schema() -> Value {
+    json!({ "type": "object" })
 }
 
 impl Default for Config {
@@ -25,21 +96,68 @@ impl Default for Config {
         // These would normally be stored in environment variables or provided in a .env file
         // For now, we'll hardcode them for simplicity
         Self {
+            profile: DEFAULT_PROFILE.to_string(),
             supabase_url: "https://your-project.supabase.co".to_string(),
             supabase_anon_key: "your-anon-key".to_string(),
             github_client_id: "your-github-client-id".to_string(),
+            github_url: None,
+            device_grant_type: None,
+            credential_store: CredentialStoreKind::default(),
+            jwt_secret: None,
+            functions: Vec::new(),
+            oauth_providers: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
+    /// URL of the project's Supabase Auth server, which is also the issuer of its tokens
+    pub fn auth_issuer(&self) -> String {
+        format!("{}/auth/v1", self.supabase_url.trim_end_matches('/'))
+    }
+    
+    /// Base URL of the GitHub instance the OAuth app lives on
+    pub fn github_url(&self) -> &str {
+        self.github_url.as_deref().unwrap_or(DEFAULT_GITHUB_URL)
+    }
+}
+
+/// The config file: named profiles plus a pointer to the default one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub default_profile: String,
+    pub profiles: BTreeMap<String, Config>,
+}
+
+/// Config file layouts we know how to read
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredConfig {
+    Profiles(ConfigFile),
+    /// Single-project config written before profiles existed
+    Legacy(Config),
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(DEFAULT_PROFILE.to_string(), Config::default());
+        
+        Self {
+            default_profile: DEFAULT_PROFILE.to_string(),
+            profiles,
+        }
+    }
+}
+
+impl ConfigFile {
     /// Load the configuration from the config file
     pub fn load() -> McpResult<Self> {
         let config_path = get_config_path()?;
         
         if !config_path.exists() {
             // Create default config if it doesn't exist
-            let config = Config::default();
+            let config = ConfigFile::default();
             config.save()?;
             return Ok(config);
         }
@@ -48,7 +166,20 @@ impl Config {
         let mut contents = String::new();
         file.read_to_string(&mut contents).map_err(to_config_error)?;
         
-        let config: Config = serde_json::from_str(&contents).map_err(to_config_error)?;
+        let stored: StoredConfig = serde_json::from_str(&contents).map_err(to_config_error)?;
+        let mut config = match stored {
+            StoredConfig::Profiles(config) => config,
+            StoredConfig::Legacy(profile) => {
+                let mut config = ConfigFile::default();
+                config.profiles.insert(DEFAULT_PROFILE.to_string(), profile);
+                config
+            }
+        };
+        
+        for (name, profile) in config.profiles.iter_mut() {
+            profile.profile = name.clone();
+        }
+        
         Ok(config)
     }
     
@@ -61,30 +192,83 @@ impl Config {
             create_dir_all(parent).map_err(to_config_error)?;
         }
         
+        // Profiles may hold a JWT secret, so only the owner may read the file
         let contents = serde_json::to_string_pretty(self).map_err(to_config_error)?;
-        let mut file = File::create(&config_path).map_err(to_config_error)?;
-        file.write_all(contents.as_bytes()).map_err(to_config_error)?;
+        write_private(&config_path, contents.as_bytes()).map_err(to_config_error)?;
+        
+        Ok(())
+    }
+    
+    /// Add a profile, or replace the settings of an existing one
+    pub fn add_profile(&mut self, name: &str, mut config: Config) {
+        config.profile = name.to_string();
+        self.profiles.insert(name.to_string(), config);
+    }
+    
+    /// Make a profile the default
+    pub fn set_default(&mut self, name: &str) -> McpResult<()> {
+        self.profile(name)?;
+        self.default_profile = name.to_string();
         
         Ok(())
     }
+    
+    /// Remove a profile and return its settings; the default profile cannot be removed
+    pub fn remove_profile(&mut self, name: &str) -> McpResult<Config> {
+        if name == self.default_profile {
+            return Err(McpError::ConfigError(format!(
+                "Profile '{}' is the default. Select another default with `rust-mcp profile use` first",
+                name
+            )));
+        }
+        
+        let config = self.profile(name)?;
+        self.profiles.remove(name);
+        
+        Ok(config)
+    }
+    
+    /// Get the settings of a named profile
+    pub fn profile(&self, name: &str) -> McpResult<Config> {
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            McpError::ConfigError(format!(
+                "Profile '{}' not found. Create it with `rust-mcp profile add {}`",
+                name, name
+            ))
+        })
+    }
 }
 
 /// Get the path to the config file
 fn get_config_path() -> McpResult<PathBuf> {
+    Ok(app_config_dir()?.join(CONFIG_FILE))
+}
+
+/// Get the directory holding the config file and other local state
+pub fn app_config_dir() -> McpResult<PathBuf> {
     let config_dir = config_dir()
         .ok_or_else(|| McpError::ConfigError("Could not find config directory".to_string()))?
         .join(APP_NAME);
     
-    Ok(config_dir.join(CONFIG_FILE))
+    Ok(config_dir)
 }
 
 /// Initialize environment from config or .env file
-pub fn init_environment() -> McpResult<Config> {
+///
+/// `profile` selects a named profile; without it the config file's default profile is used.
+pub fn init_environment(profile: Option<&str>) -> McpResult<Config> {
     // Try to load from .env file if it exists
     let _ = dotenv::dotenv();
     
-    // Load the config (or create default)
-    let mut config = Config::load()?;
+    // Load the config (or create default) and pick the profile
+    let config_file = ConfigFile::load()?;
+    let name = profile.unwrap_or(&config_file.default_profile);
+    let mut config = config_file.profile(name)?;
+    
+    // An explicitly selected profile wins over the environment
+    if profile.is_some() {
+        return Ok(config);
+    }
     
     // Override with environment variables if present
     if let Ok(url) = std::env::var("SUPABASE_URL") {
@@ -99,5 +283,9 @@ pub fn init_environment() -> McpResult<Config> {
         config.github_client_id = client_id;
     }
     
+    if let Ok(secret) = std::env::var("SUPABASE_JWT_SECRET") {
+        config.jwt_secret = Some(secret);
+    }
+    
     Ok(config)
 }
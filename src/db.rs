@@ -0,0 +1,301 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use std::io::Read;
+
+use crate::error::{McpError, McpResult};
+use crate::postgrest::ContentRange;
+
+/// Widest a table cell gets before it is cut short
+const MAX_CELL_WIDTH: usize = 40;
+
+/// How `db` commands print rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns
+    Table,
+    /// One pretty-printed JSON document
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Split a `--filter` value such as `status=eq.active` into column and PostgREST condition
+pub fn parse_filter(filter: &str) -> McpResult<(String, String)> {
+    match filter.split_once('=') {
+        Some((column, condition)) if !column.is_empty() && condition.contains('.') => {
+            Ok((column.to_string(), condition.to_string()))
+        }
+        _ => Err(McpError::InvalidArguments(format!(
+            "Filter '{}' is not of the form column=operator.value, e.g. id=eq.42",
+            filter
+        ))),
+    }
+}
+
+/// Split an `--order` value such as `created_at.desc` into column and whether it ascends
+pub fn parse_order(order: &str) -> (String, bool) {
+    match order.rsplit_once('.') {
+        Some((column, "desc")) => (column.to_string(), false),
+        Some((column, "asc")) => (column.to_string(), true),
+        _ => (order.to_string(), true),
+    }
+}
+
+/// Turn `--offset` and `--limit` into the first and last row to request, both inclusive
+pub fn row_range(offset: u64, limit: u64) -> McpResult<(u64, u64)> {
+    if limit == 0 {
+        return Err(McpError::InvalidArguments(
+            "--limit must be at least 1".to_string(),
+        ));
+    }
+
+    let last = offset.checked_add(limit - 1).ok_or_else(|| {
+        McpError::InvalidArguments(format!(
+            "--offset {} with --limit {} goes past the last possible row",
+            offset, limit
+        ))
+    })?;
+    Ok((offset, last))
+}
+
+/// Read rows to insert from a JSON file, or from stdin when `path` is `-`
+pub fn read_rows(path: &str) -> McpResult<Value> {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(path)?
+    };
+
+    let rows: Value = serde_json::from_str(&text)
+        .map_err(|e| McpError::InvalidArguments(format!("{} is not valid JSON: {}", path, e)))?;
+
+    if !rows.is_object() && !rows.is_array() {
+        return Err(McpError::InvalidArguments(format!(
+            "{} must hold a JSON object or an array of objects",
+            path
+        )));
+    }
+
+    Ok(rows)
+}
+
+/// Print a query result in the chosen format
+///
+/// Arrays of objects are printed as rows; anything else, such as the scalar result of a
+/// function, is printed as JSON.
+pub fn print_rows(data: &Value, format: OutputFormat) -> McpResult<()> {
+    let rows = match data.as_array() {
+        Some(rows) => rows,
+        None => {
+            println!("{}", serde_json::to_string_pretty(data)?);
+            return Ok(());
+        }
+    };
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(data)?),
+        OutputFormat::Ndjson => {
+            for row in rows {
+                println!("{}", serde_json::to_string(row)?);
+            }
+        }
+        OutputFormat::Table => print_table(rows),
+    }
+
+    Ok(())
+}
+
+/// Report which rows were returned, and of how many, from a `Content-Range` header
+pub fn print_content_range(content_range: Option<ContentRange>) {
+    let Some(ContentRange { rows, total }) = content_range else {
+        return;
+    };
+
+    let total = total
+        .map(|total| total.to_string())
+        .unwrap_or_else(|| "?".to_string());
+    match rows {
+        Some((first, last)) => eprintln!(
+            "Rows {}-{} of {}",
+            first.saturating_add(1),
+            last.saturating_add(1),
+            total
+        ),
+        None => eprintln!("No rows of {}", total),
+    }
+}
+
+fn print_table(rows: &[Value]) {
+    // Columns in the order they first appear; rows that are not objects get a `value` column
+    let mut columns: Vec<String> = Vec::new();
+    for row in rows {
+        match row.as_object() {
+            Some(object) => {
+                for key in object.keys() {
+                    if !columns.contains(key) {
+                        columns.push(key.clone());
+                    }
+                }
+            }
+            None if !columns.iter().any(|column| column == "value") => {
+                columns.push("value".to_string())
+            }
+            None => {}
+        }
+    }
+
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| match row.as_object() {
+                    Some(object) => cell_text(object.get(column).unwrap_or(&Value::Null)),
+                    None if column == "value" => cell_text(row),
+                    None => String::new(),
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let line = |values: &[String]| {
+        let padded: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect();
+        println!("{}", padded.join(" | ").trim_end());
+    };
+
+    if !columns.is_empty() {
+        line(&columns);
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        println!("{}", rule.join("-+-"));
+        for row in &cells {
+            line(row);
+        }
+    }
+
+    let noun = if rows.len() == 1 { "row" } else { "rows" };
+    println!("({} {})", rows.len(), noun);
+}
+
+/// Text of one table cell: strings unquoted, `null` empty, everything else as compact JSON
+fn cell_text(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    let text = text.replace(['\n', '\t'], " ");
+
+    if text.chars().count() > MAX_CELL_WIDTH {
+        let cut: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+        format!("{}…", cut)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(
+            parse_filter("status=eq.active").unwrap(),
+            ("status".to_string(), "eq.active".to_string())
+        );
+        // Only the first `=` separates the column
+        assert_eq!(
+            parse_filter("note=eq.a=b").unwrap(),
+            ("note".to_string(), "eq.a=b".to_string())
+        );
+        assert_eq!(
+            parse_filter("deleted_at=not.is.null").unwrap(),
+            ("deleted_at".to_string(), "not.is.null".to_string())
+        );
+
+        for invalid in ["status", "=eq.active", "status=active", ""] {
+            assert!(matches!(
+                parse_filter(invalid),
+                Err(McpError::InvalidArguments(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn row_ranges_are_inclusive_and_checked() {
+        assert_eq!(row_range(0, 20).unwrap(), (0, 19));
+        assert_eq!(row_range(40, 1).unwrap(), (40, 40));
+        assert_eq!(row_range(u64::MAX, 1).unwrap(), (u64::MAX, u64::MAX));
+        assert_eq!(row_range(1, u64::MAX).unwrap(), (1, u64::MAX));
+
+        for (offset, limit) in [(0, 0), (u64::MAX, 2), (2, u64::MAX)] {
+            assert!(matches!(
+                row_range(offset, limit),
+                Err(McpError::InvalidArguments(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_order() {
+        assert_eq!(
+            parse_order("created_at.desc"),
+            ("created_at".to_string(), false)
+        );
+        assert_eq!(
+            parse_order("created_at.asc"),
+            ("created_at".to_string(), true)
+        );
+        assert_eq!(parse_order("created_at"), ("created_at".to_string(), true));
+        // Only a known direction is split off
+        assert_eq!(
+            parse_order("author.name"),
+            ("author.name".to_string(), true)
+        );
+        assert_eq!(
+            parse_order("author.name.desc"),
+            ("author.name".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn formats_cells() {
+        assert_eq!(cell_text(&Value::Null), "");
+        assert_eq!(cell_text(&json!("plain")), "plain");
+        assert_eq!(cell_text(&json!(42)), "42");
+        assert_eq!(cell_text(&json!({ "a": [1, 2] })), r#"{"a":[1,2]}"#);
+        assert_eq!(cell_text(&json!("two\nlines\there")), "two lines here");
+    }
+
+    #[test]
+    fn truncates_long_cells() {
+        let exact = "x".repeat(MAX_CELL_WIDTH);
+        assert_eq!(cell_text(&json!(exact)), exact);
+
+        let long = "é".repeat(MAX_CELL_WIDTH + 1);
+        let text = cell_text(&json!(long));
+        assert_eq!(text.chars().count(), MAX_CELL_WIDTH);
+        assert!(text.ends_with('…'));
+        assert!(text.starts_with(&"é".repeat(MAX_CELL_WIDTH - 1)));
+    }
+}
@@ -0,0 +1,163 @@
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::{McpError, McpResult};
+
+/// Grant type of device access token requests (RFC 8628)
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Scopes requested for the GitHub token
+const SCOPES: &str = "read:user user:email";
+
+/// Polling interval when GitHub does not name one, in seconds
+const DEFAULT_INTERVAL: u64 = 5;
+
+/// Added to the polling interval whenever GitHub answers `slow_down`
+const SLOW_DOWN_STEP: Duration = Duration::from_secs(5);
+
+/// A device code waiting for the user to enter it
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    /// The code the user types in at `verification_uri`
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Lifetime of the codes, in seconds
+    pub expires_in: u64,
+    /// Minimum wait between token requests, in seconds
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    DEFAULT_INTERVAL
+}
+
+/// Answer of the token endpoint, which reports a pending authorization as an error
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+    interval: Option<u64>,
+}
+
+/// The OAuth device authorization grant with GitHub, for hosts that cannot open a browser
+pub struct GitHubDeviceFlow {
+    client: Client,
+    github_url: String,
+    client_id: String,
+}
+
+impl GitHubDeviceFlow {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            client: Client::new(),
+            github_url: config.github_url().trim_end_matches('/').to_string(),
+            client_id: config.github_client_id.clone(),
+        }
+    }
+
+    /// Request a device code and the code for the user to enter
+    pub async fn start(&self) -> McpResult<DeviceAuthorization> {
+        let response = self
+            .client
+            .post(format!("{}/login/device/code", self.github_url))
+            .header("Accept", "application/json")
+            .form(&[("client_id", self.client_id.as_str()), ("scope", SCOPES)])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(McpError::AuthError(format!(
+                "Device code request failed: HTTP {}",
+                response.status()
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Poll until the user authorizes the device, then return the GitHub access token
+    pub async fn wait_for_token(&self, authorization: &DeviceAuthorization) -> McpResult<String> {
+        let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval = Duration::from_secs(authorization.interval);
+
+        loop {
+            tokio::time::sleep(interval).await;
+            if Instant::now() >= deadline {
+                return Err(expired());
+            }
+
+            let response = self
+                .client
+                .post(format!("{}/login/oauth/access_token", self.github_url))
+                .header("Accept", "application/json")
+                .form(&[
+                    ("client_id", self.client_id.as_str()),
+                    ("device_code", authorization.device_code.as_str()),
+                    ("grant_type", DEVICE_CODE_GRANT),
+                ])
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(McpError::AuthError(format!(
+                    "Device token request failed: HTTP {}",
+                    response.status()
+                )));
+            }
+
+            let token: TokenResponse = response.json().await?;
+            if let Some(access_token) = token.access_token {
+                return Ok(access_token);
+            }
+
+            match token.error.as_deref() {
+                Some("authorization_pending") => {}
+                Some("slow_down") => {
+                    interval = token
+                        .interval
+                        .map(Duration::from_secs)
+                        .unwrap_or(interval + SLOW_DOWN_STEP);
+                }
+                Some("expired_token") => return Err(expired()),
+                Some("access_denied") => {
+                    return Err(McpError::AuthError("Authorization was denied".to_string()));
+                }
+                error => {
+                    let description = token
+                        .error_description
+                        .as_deref()
+                        .or(error)
+                        .unwrap_or("no access token in the response");
+                    return Err(McpError::AuthError(format!(
+                        "Device authorization failed: {}",
+                        description
+                    )));
+                }
+            }
+        }
+    }
+}
+
+fn expired() -> McpError {
+    McpError::AuthError("The device code expired before it was entered".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_defaults_when_github_names_none() {
+        let authorization: DeviceAuthorization = serde_json::from_str(
+            r#"{"device_code":"d","user_code":"ABCD-1234","verification_uri":"https://github.com/login/device","expires_in":900}"#,
+        )
+        .unwrap();
+        assert_eq!(authorization.interval, DEFAULT_INTERVAL);
+        assert_eq!(authorization.user_code, "ABCD-1234");
+    }
+}
@@ -0,0 +1,216 @@
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::{McpError, McpResult, to_auth_error};
+use crate::pkce::CODE_CHALLENGE_METHOD;
+
+/// OAuth 2.0 Authorization Server Metadata (RFC 8414), or the OpenID Provider equivalent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationServerMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_challenge_methods_supported: Option<Vec<String>>,
+}
+
+/// OAuth 2.0 Protected Resource Metadata (RFC 9728)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectedResourceMetadata {
+    /// URL of the protected resource
+    pub resource: String,
+    /// Issuers of the authorization servers that hand out tokens for the resource
+    pub authorization_servers: Vec<String>,
+    #[serde(default)]
+    pub bearer_methods_supported: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_name: Option<String>,
+}
+
+impl AuthorizationServerMetadata {
+    /// Fetch the metadata published for `issuer`
+    ///
+    /// Tries the RFC 8414 and OpenID Connect well-known locations, both inserted before the
+    /// issuer's path and appended to it. Returns `None` when none of them exists. `api_key` is
+    /// sent as the `apikey` header the Supabase API gateway requires.
+    pub async fn discover(client: &Client, issuer: &str, api_key: &str) -> McpResult<Option<Self>> {
+        for url in metadata_urls(issuer)? {
+            let response = client
+                .get(url)
+                .header("apikey", api_key)
+                .send()
+                .await
+                .map_err(McpError::HttpError)?;
+
+            if response.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+            if !response.status().is_success() {
+                return Err(McpError::AuthError(format!(
+                    "Failed to fetch authorization server metadata: HTTP {}",
+                    response.status()
+                )));
+            }
+
+            let metadata = response.json::<Self>().await.map_err(McpError::HttpError)?;
+            metadata.check(issuer)?;
+
+            return Ok(Some(metadata));
+        }
+
+        Ok(None)
+    }
+
+    /// Endpoints Supabase Auth serves under `issuer`, for projects that publish no metadata
+    pub fn supabase_defaults(issuer: &str) -> Self {
+        let issuer = issuer.trim_end_matches('/');
+
+        Self {
+            issuer: issuer.to_string(),
+            authorization_endpoint: format!("{}/authorize", issuer),
+            token_endpoint: format!("{}/token", issuer),
+            jwks_uri: Some(format!("{}/.well-known/jwks.json", issuer)),
+            code_challenge_methods_supported: Some(vec![CODE_CHALLENGE_METHOD.to_string()]),
+        }
+    }
+
+    /// Reject metadata for another issuer, or for a server that cannot do PKCE with S256
+    fn check(&self, issuer: &str) -> McpResult<()> {
+        if self.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(McpError::AuthError(format!(
+                "Authorization server metadata is for issuer {}, expected {}",
+                self.issuer, issuer
+            )));
+        }
+
+        if let Some(methods) = &self.code_challenge_methods_supported
+            && !methods.iter().any(|method| method == CODE_CHALLENGE_METHOD)
+        {
+            return Err(McpError::AuthError(format!(
+                "Authorization server does not support {} code challenges",
+                CODE_CHALLENGE_METHOD
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl ProtectedResourceMetadata {
+    /// Fetch the metadata published for the resource at `resource`
+    ///
+    /// Tries the RFC 9728 location, with the well-known segment inserted before the resource's
+    /// path, then the one for the host as a whole. Returns `None` when neither exists.
+    pub async fn discover(client: &Client, resource: &Url) -> McpResult<Option<Self>> {
+        let path = resource.path().trim_end_matches('/');
+
+        let mut urls = Vec::new();
+        for suffix in [path, ""] {
+            let mut url = resource.clone();
+            url.set_path(&format!("/.well-known/oauth-protected-resource{}", suffix));
+            url.set_query(None);
+            urls.push(url);
+        }
+        urls.dedup();
+
+        for url in urls {
+            let response = client.get(url).send().await.map_err(McpError::HttpError)?;
+            if !response.status().is_success() {
+                continue;
+            }
+
+            let metadata = response.json::<Self>().await.map_err(McpError::HttpError)?;
+            return Ok(Some(metadata));
+        }
+
+        Ok(None)
+    }
+
+    /// Whether `issuer` hands out tokens for the resource
+    pub fn names_authorization_server(&self, issuer: &str) -> bool {
+        let issuer = issuer.trim_end_matches('/');
+        self.authorization_servers
+            .iter()
+            .any(|server| server.trim_end_matches('/') == issuer)
+    }
+}
+
+/// Well-known metadata locations for `issuer`, most specific first
+fn metadata_urls(issuer: &str) -> McpResult<Vec<Url>> {
+    let issuer = Url::parse(issuer).map_err(to_auth_error)?;
+    let path = issuer.path().trim_end_matches('/');
+
+    let mut urls = Vec::new();
+    for document in ["oauth-authorization-server", "openid-configuration"] {
+        // RFC 8414: the well-known segment goes between the host and the issuer's path
+        let mut url = issuer.clone();
+        url.set_path(&format!("/.well-known/{}{}", document, path));
+        urls.push(url);
+
+        // OpenID Connect Discovery, which Supabase Auth follows for both documents
+        let mut url = issuer.clone();
+        url.set_path(&format!("{}/.well-known/{}", path, document));
+        urls.push(url);
+    }
+
+    urls.dedup();
+    Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_urls_cover_both_documents_and_placements() {
+        let urls: Vec<String> = metadata_urls("https://abc.supabase.co/auth/v1/")
+            .unwrap()
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(
+            urls,
+            [
+                "https://abc.supabase.co/.well-known/oauth-authorization-server/auth/v1",
+                "https://abc.supabase.co/auth/v1/.well-known/oauth-authorization-server",
+                "https://abc.supabase.co/.well-known/openid-configuration/auth/v1",
+                "https://abc.supabase.co/auth/v1/.well-known/openid-configuration",
+            ]
+        );
+
+        // Without a path both placements are the same URL
+        assert_eq!(metadata_urls("https://auth.example.com").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn check_rejects_other_issuers_and_plain_challenges() {
+        let issuer = "https://abc.supabase.co/auth/v1";
+        let mut metadata = AuthorizationServerMetadata::supabase_defaults(issuer);
+        assert_eq!(metadata.token_endpoint, format!("{}/token", issuer));
+        assert!(metadata.check(&format!("{}/", issuer)).is_ok());
+        assert!(metadata.check("https://other.supabase.co/auth/v1").is_err());
+
+        metadata.code_challenge_methods_supported = Some(vec!["plain".to_string()]);
+        assert!(metadata.check(issuer).is_err());
+
+        metadata.code_challenge_methods_supported = None;
+        assert!(metadata.check(issuer).is_ok());
+    }
+
+    #[test]
+    fn names_authorization_server_ignores_trailing_slashes() {
+        let metadata = ProtectedResourceMetadata {
+            resource: "https://mcp.example.com/mcp".to_string(),
+            authorization_servers: vec!["https://abc.supabase.co/auth/v1/".to_string()],
+            bearer_methods_supported: Vec::new(),
+            resource_name: None,
+        };
+
+        assert!(metadata.names_authorization_server("https://abc.supabase.co/auth/v1"));
+        assert!(!metadata.names_authorization_server("https://evil.example.com/auth/v1"));
+    }
+}
@@ -23,13 +23,74 @@ pub enum McpError {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("OAuth state mismatch: the callback did not come from this login attempt")]
+    StateMismatch,
+
+    #[error("Invalid access token: {0}")]
+    InvalidToken(String),
+
+    #[error("Email address not confirmed. Open the confirmation link sent to it, then log in again.")]
+    EmailNotConfirmed,
+
+    #[error("Session expired. Please login again.")]
+    SessionExpired,
+
     #[error("Not authenticated. Please login first.")]
     NotAuthenticated,
 
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+
+    #[error("MCP server error: {0}")]
+    RemoteError(String),
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Edge Function error: {0}")]
+    FunctionError(String),
+
+    #[error("Realtime error: {0}")]
+    RealtimeError(String),
+
+    #[error("Resource not found: {0}")]
+    ResourceNotFound(String),
+
     #[error("Unexpected error: {0}")]
     Other(String),
 }
 
+impl McpError {
+    /// Stable, machine-readable name for the kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            McpError::AuthError(_) => "auth_error",
+            McpError::BrowserError(_) => "browser_error",
+            McpError::HttpError(_) => "http_error",
+            McpError::CredentialError(_) => "credential_error",
+            McpError::ConfigError(_) => "config_error",
+            McpError::SerdeError(_) => "serialization_error",
+            McpError::IoError(_) => "io_error",
+            McpError::StateMismatch => "state_mismatch",
+            McpError::InvalidToken(_) => "invalid_token",
+            McpError::EmailNotConfirmed => "email_not_confirmed",
+            McpError::SessionExpired => "session_expired",
+            McpError::NotAuthenticated => "not_authenticated",
+            McpError::InvalidArguments(_) => "invalid_arguments",
+            McpError::RemoteError(_) => "remote_error",
+            McpError::DatabaseError(_) => "database_error",
+            McpError::StorageError(_) => "storage_error",
+            McpError::FunctionError(_) => "function_error",
+            McpError::RealtimeError(_) => "realtime_error",
+            McpError::ResourceNotFound(_) => "resource_not_found",
+            McpError::Other(_) => "other",
+        }
+    }
+}
+
 pub type McpResult<T> = Result<T, McpError>;
 
 // Helper functions for common error conversions
@@ -0,0 +1,183 @@
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, Response};
+use std::io::Read;
+
+use crate::error::{McpError, McpResult};
+use crate::supabase::SupabaseClient;
+
+impl SupabaseClient {
+    /// Call the Edge Function `name` as the owner of `token`
+    ///
+    /// `headers` should include the body's `Content-Type`; they replace the default headers of
+    /// the same name, such as `Authorization`. The response is returned unread so callers can
+    /// stream it, and is not cut off however long the function runs. Non-success statuses are
+    /// turned into a `FunctionError`.
+    pub async fn invoke_function(
+        &self,
+        token: &str,
+        name: &str,
+        body: Option<Vec<u8>>,
+        headers: &[(String, String)],
+    ) -> McpResult<Response> {
+        let url = self.api_url(&format!(
+            "functions/v1/{}",
+            utf8_percent_encode(name, NON_ALPHANUMERIC)
+        ));
+
+        let mut overrides = HeaderMap::new();
+        for (header, value) in headers {
+            let name =
+                HeaderName::from_bytes(header.as_bytes()).map_err(|_| invalid_header(header))?;
+            let value = HeaderValue::from_str(value).map_err(|_| invalid_header(header))?;
+            overrides.append(name, value);
+        }
+
+        let mut request = self
+            .transfer_request(Method::POST, &url, token)
+            .headers(overrides);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+
+        let response = request.send().await.map_err(McpError::HttpError)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = match body.trim() {
+                "" => format!("{} returned HTTP {}", name, status),
+                body => format!("{} returned HTTP {}: {}", name, status, body),
+            };
+            return Err(McpError::FunctionError(message));
+        }
+
+        Ok(response)
+    }
+}
+
+fn invalid_header(name: &str) -> McpError {
+    McpError::InvalidArguments(format!("Header '{}' has an invalid name or value", name))
+}
+
+/// Read a `--data` value: `@file`, `@-` for stdin, or the body itself
+pub fn read_data(data: &str) -> McpResult<Vec<u8>> {
+    match data.strip_prefix('@') {
+        Some("-") => {
+            let mut body = Vec::new();
+            std::io::stdin().read_to_end(&mut body)?;
+            Ok(body)
+        }
+        Some(path) => Ok(std::fs::read(path)?),
+        None => Ok(data.as_bytes().to_vec()),
+    }
+}
+
+/// Content type to send a `--data` body with when none is given
+pub fn content_type_of(body: &[u8]) -> &'static str {
+    if serde_json::from_slice::<serde_json::Value>(body).is_ok() {
+        "application/json"
+    } else if std::str::from_utf8(body).is_ok() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Split a `--header` value such as `X-Trace: abc`
+pub fn parse_header(header: &str) -> McpResult<(String, String)> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(McpError::InvalidArguments(format!(
+            "Header '{}' is not of the form 'Name: value'",
+            header
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::supabase::REQUEST_TIMEOUT;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn slow_functions_stream_and_caller_headers_replace_defaults() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let supabase = SupabaseClient::new(Config {
+            supabase_url: format!("http://{}", listener.local_addr().unwrap()),
+            supabase_anon_key: "anon".to_string(),
+            ..Config::default()
+        });
+
+        // Answer with a line per half of the request timeout, so the whole reply outlasts it
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(socket.read_u8().await.unwrap());
+            }
+
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\r\n")
+                .await
+                .unwrap();
+            for line in [b"1\n", b"2\n", b"3\n"] {
+                tokio::time::sleep(REQUEST_TIMEOUT / 2).await;
+                socket.write_all(line).await.unwrap();
+            }
+            String::from_utf8(head).unwrap().to_ascii_lowercase()
+        });
+
+        let headers = [
+            ("Authorization".to_string(), "Bearer service".to_string()),
+            ("X-Trace".to_string(), "abc".to_string()),
+        ];
+        let response = supabase
+            .invoke_function("user", "report", None, &headers)
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "1\n2\n3\n");
+
+        let head = server.await.unwrap();
+        assert_eq!(head.matches("authorization:").count(), 1, "{}", head);
+        assert!(
+            head.contains("authorization: bearer service\r\n"),
+            "{}",
+            head
+        );
+        assert!(head.contains("apikey: anon\r\n"), "{}", head);
+        assert!(head.contains("x-trace: abc\r\n"), "{}", head);
+    }
+
+    #[test]
+    fn data_is_inline_or_read_from_a_file() {
+        assert_eq!(read_data("{\"a\":1}").unwrap(), b"{\"a\":1}");
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"from file").unwrap();
+        let data = read_data(&format!("@{}", file.path().display())).unwrap();
+        assert_eq!(data, b"from file");
+    }
+
+    #[test]
+    fn content_type_follows_the_body() {
+        assert_eq!(content_type_of(b"{\"a\":1}"), "application/json");
+        assert_eq!(content_type_of(b"hello"), "text/plain");
+        assert_eq!(content_type_of(&[0xff, 0xfe]), "application/octet-stream");
+    }
+
+    #[test]
+    fn parses_headers() {
+        assert_eq!(
+            parse_header("X-Trace:  abc ").unwrap(),
+            ("X-Trace".to_string(), "abc".to_string())
+        );
+        assert!(parse_header("X-Trace").is_err());
+        assert!(parse_header(": abc").is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, app_config_dir};
+use crate::error::{McpError, McpResult};
+use crate::session::now_secs;
+use crate::supabase::SupabaseClient;
+
+/// Audience Supabase puts in access tokens of signed-in users
+const AUDIENCE: &str = "authenticated";
+
+/// Clock skew tolerated when checking `exp` and `iat`, in seconds
+const LEEWAY: u64 = 30;
+
+/// How long a cached JWKS is trusted before it is fetched again, in seconds
+const JWKS_CACHE_TTL: u64 = 600;
+
+/// Claims of a Supabase access token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenClaims {
+    pub sub: String,
+    pub iss: Option<String>,
+    pub aud: Option<Audience>,
+    pub exp: u64,
+    pub iat: Option<u64>,
+    pub email: Option<String>,
+    pub role: Option<String>,
+    /// Authenticator assurance level (`aal1`, `aal2`)
+    pub aal: Option<String>,
+    pub session_id: Option<String>,
+    /// Space-separated OAuth scopes, present on tokens issued to OAuth clients
+    pub scope: Option<String>,
+    pub is_anonymous: Option<bool>,
+    #[serde(default)]
+    pub app_metadata: serde_json::Value,
+    #[serde(default)]
+    pub user_metadata: serde_json::Value,
+}
+
+/// The `aud` claim, which may be a single value or a list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl std::fmt::Display for Audience {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Audience::One(aud) => write!(f, "{}", aud),
+            Audience::Many(auds) => write!(f, "{}", auds.join(", ")),
+        }
+    }
+}
+
+/// How a token's signature was checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureCheck {
+    /// Verified against a key from the project's JWKS
+    Jwks,
+    /// Verified with the configured HS256 secret
+    Secret,
+}
+
+/// Decodes and validates Supabase access tokens against the configured project
+pub struct TokenValidator {
+    config: Config,
+    supabase: SupabaseClient,
+}
+
+/// JWKS as cached on disk
+#[derive(Serialize, Deserialize)]
+struct CachedJwks {
+    fetched_at: u64,
+    jwks: JwkSet,
+}
+
+impl TokenValidator {
+    /// Create a validator for the given profile
+    pub fn new(config: Config) -> Self {
+        let supabase = SupabaseClient::new(config.clone());
+        Self { config, supabase }
+    }
+
+    /// Validate the claims of a token without checking its signature
+    pub fn decode(&self, token: &str) -> McpResult<TokenClaims> {
+        let header = decode_header(token).map_err(to_token_error)?;
+
+        let mut validation = self.validation(header.alg);
+        validation.insecure_disable_signature_validation();
+
+        self.decode_with(token, &DecodingKey::from_secret(&[]), &validation)
+    }
+
+    /// Whether the signature of a token can be checked without asking Supabase
+    ///
+    /// HS256 tokens cannot be unless the project's `jwt_secret` is configured.
+    pub fn can_verify(&self, token: &str) -> bool {
+        decode_header(token)
+            .is_ok_and(|header| header.alg != Algorithm::HS256 || self.config.jwt_secret.is_some())
+    }
+
+    /// Validate the claims of a token and verify its signature
+    ///
+    /// HS256 tokens need the configured `jwt_secret`; asymmetric tokens are checked against
+    /// the project's JWKS, which is cached on disk.
+    pub async fn verify(&self, token: &str) -> McpResult<(TokenClaims, SignatureCheck)> {
+        let header = decode_header(token).map_err(to_token_error)?;
+        let validation = self.validation(header.alg);
+
+        if header.alg == Algorithm::HS256 {
+            let secret = self.config.jwt_secret.as_deref().ok_or_else(|| {
+                McpError::InvalidToken(
+                    "HS256 tokens can only be verified with a configured jwt_secret".to_string(),
+                )
+            })?;
+            let key = DecodingKey::from_secret(secret.as_bytes());
+            let claims = self.decode_with(token, &key, &validation)?;
+            return Ok((claims, SignatureCheck::Secret));
+        }
+
+        let kid = header
+            .kid
+            .ok_or_else(|| McpError::InvalidToken("Token header has no key ID".to_string()))?;
+        let jwks = self.jwks(&kid).await?;
+        let jwk = jwks
+            .find(&kid)
+            .ok_or_else(|| McpError::InvalidToken(format!("Unknown signing key '{}'", kid)))?;
+
+        let key = DecodingKey::from_jwk(jwk).map_err(to_token_error)?;
+        let claims = self.decode_with(token, &key, &validation)?;
+
+        Ok((claims, SignatureCheck::Jwks))
+    }
+
+    fn validation(&self, alg: Algorithm) -> Validation {
+        let mut validation = Validation::new(alg);
+        validation.leeway = LEEWAY;
+        validation.set_audience(&[AUDIENCE]);
+        validation.set_issuer(&[self.config.auth_issuer()]);
+        validation.set_required_spec_claims(&["exp", "sub", "aud", "iss"]);
+        validation
+    }
+
+    fn decode_with(
+        &self,
+        token: &str,
+        key: &DecodingKey,
+        validation: &Validation,
+    ) -> McpResult<TokenClaims> {
+        let claims = decode::<TokenClaims>(token, key, validation)
+            .map_err(to_token_error)?
+            .claims;
+
+        // jsonwebtoken does not check `iat`; reject tokens issued in the future
+        if claims.iat.is_some_and(|iat| iat > now_secs() + LEEWAY) {
+            return Err(McpError::InvalidToken(
+                "Token was issued in the future".to_string(),
+            ));
+        }
+
+        Ok(claims)
+    }
+
+    /// Get a JWKS containing `kid`, from the disk cache when it is fresh
+    async fn jwks(&self, kid: &str) -> McpResult<JwkSet> {
+        let cache_path = self.jwks_cache_path();
+
+        if let Some(cached) = cache_path.as_deref().and_then(read_cache)
+            && now_secs() < cached.fetched_at + JWKS_CACHE_TTL
+            && cached.jwks.find(kid).is_some()
+        {
+            return Ok(cached.jwks);
+        }
+
+        let jwks = self.supabase.get_jwks().await?;
+
+        // A cache that cannot be written only costs a refetch next time
+        if let Some(path) = cache_path {
+            let cached = CachedJwks {
+                fetched_at: now_secs(),
+                jwks,
+            };
+            if let Ok(contents) = serde_json::to_string(&cached) {
+                let _ = path.parent().map(fs::create_dir_all);
+                let _ = fs::write(&path, contents);
+            }
+            return Ok(cached.jwks);
+        }
+
+        Ok(jwks)
+    }
+
+    /// Forget the cached JWKS of the profile
+    pub fn clear_cache(&self) {
+        if let Some(path) = self.jwks_cache_path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn jwks_cache_path(&self) -> Option<PathBuf> {
+        app_config_dir().ok().map(|dir| {
+            dir.join("jwks")
+                .join(format!("{}.json", self.config.profile))
+        })
+    }
+}
+
+fn read_cache(path: &Path) -> Option<CachedJwks> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn to_token_error(e: jsonwebtoken::errors::Error) -> McpError {
+    McpError::InvalidToken(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde_json::json;
+
+    const SUPABASE_URL: &str = "https://abc.supabase.co";
+    const SECRET: &str = "secret";
+
+    fn validator(jwt_secret: Option<&str>) -> TokenValidator {
+        TokenValidator::new(Config {
+            supabase_url: SUPABASE_URL.to_string(),
+            jwt_secret: jwt_secret.map(str::to_string),
+            ..Config::default()
+        })
+    }
+
+    fn token(claims: serde_json::Value, secret: &str) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn claims() -> serde_json::Value {
+        json!({
+            "sub": "user",
+            "iss": format!("{}/auth/v1", SUPABASE_URL),
+            "aud": "authenticated",
+            "exp": now_secs() + 3600,
+            "iat": now_secs(),
+        })
+    }
+
+    #[tokio::test]
+    async fn hs256_tokens_need_the_secret() {
+        let token = token(claims(), SECRET);
+
+        assert!(!validator(None).can_verify(&token));
+        assert!(validator(None).verify(&token).await.is_err());
+        assert_eq!(validator(None).decode(&token).unwrap().sub, "user");
+
+        let validator = validator(Some(SECRET));
+        assert!(validator.can_verify(&token));
+        let (claims, check) = validator.verify(&token).await.unwrap();
+        assert_eq!(claims.sub, "user");
+        assert_eq!(check, SignatureCheck::Secret);
+    }
+
+    #[tokio::test]
+    async fn bad_signatures_and_claims_are_rejected() {
+        let validator = validator(Some(SECRET));
+        assert!(validator.verify(&token(claims(), "other")).await.is_err());
+
+        let mut wrong_issuer = claims();
+        wrong_issuer["iss"] = json!("https://other.supabase.co/auth/v1");
+        assert!(validator.decode(&token(wrong_issuer, SECRET)).is_err());
+
+        let mut expired = claims();
+        expired["exp"] = json!(now_secs() - 3600);
+        assert!(validator.decode(&token(expired, SECRET)).is_err());
+
+        let mut future = claims();
+        future["iat"] = json!(now_secs() + 3600);
+        assert!(validator.decode(&token(future, SECRET)).is_err());
+    }
+
+    #[test]
+    fn audience_lists_are_joined() {
+        let audience: Audience = serde_json::from_value(json!(["a", "b"])).unwrap();
+        assert_eq!(audience.to_string(), "a, b");
+    }
+}
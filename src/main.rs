@@ -1,15 +1,48 @@
 mod auth;
 mod browser;
 mod config;
+mod credentials;
+mod db;
+mod device;
+mod discovery;
 mod error;
+mod functions;
+mod jwt;
+mod mcp;
+mod pkce;
+mod postgrest;
+mod provider;
+mod realtime;
+mod session;
+mod storage;
 mod supabase;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::process;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use url::Url;
 
 use crate::auth::AuthHandler;
-use crate::config::init_environment;
-use crate::error::McpResult;
+use crate::config::{Config, ConfigFile, init_environment};
+use crate::credentials::CredentialStoreKind;
+use crate::db::{OutputFormat, parse_filter, parse_order, print_content_range, print_rows, read_rows, row_range};
+use crate::error::{McpError, McpResult};
+use crate::functions::{content_type_of, parse_header, read_data};
+use crate::mcp::client::{
+    McpClient, print_list, print_prompt, print_resource_contents, print_server_info,
+    print_tool_result,
+};
+use crate::mcp::http::serve_http;
+use crate::mcp::proxy::run_proxy;
+use crate::mcp::server::McpServer;
+use crate::mcp::stdio::serve_stdio;
+use crate::provider::{DEFAULT_PROVIDER, OAuthProvider};
+use crate::realtime::{ChannelOptions, PostgresChangesFilter, RealtimeEvent};
+use crate::storage::{ObjectPath, local_destination, remote_destination};
+use crate::supabase::LogoutScope;
 
 #[derive(Parser)]
 #[clap(
@@ -18,20 +51,491 @@ use crate::error::McpResult;
     version
 )]
 struct Cli {
+    /// Named profile to use instead of the default one
+    #[clap(long, global = true, env = "RUST_MCP_PROFILE")]
+    profile: Option<String>,
+    
     #[clap(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Sign up with a new GitHub account
-    Signup,
+    /// Sign up through an OAuth provider (GitHub by default), or with an email and password
+    Signup {
+        /// OAuth provider enabled on the project, e.g. google, gitlab, azure or keycloak
+        #[clap(long, default_value = DEFAULT_PROVIDER, conflicts_with = "email")]
+        provider: String,
+        
+        /// Sign up with this email address and a password instead of an OAuth provider
+        #[clap(long, value_name = "EMAIL")]
+        email: Option<String>,
+        
+        #[clap(flatten)]
+        password: PasswordArgs,
+    },
     
-    /// Login with an existing GitHub account
-    Login,
+    /// Log in through an OAuth provider (GitHub by default), or with an email address
+    Login {
+        /// OAuth provider enabled on the project, e.g. google, gitlab, azure or keycloak
+        #[clap(long, default_value = DEFAULT_PROVIDER, conflicts_with_all = ["email", "device"])]
+        provider: String,
+        
+        /// Use GitHub's device flow: enter a code on another device instead of opening a browser
+        ///
+        /// Needs an auth server that accepts GitHub access tokens, named by `device_grant_type`.
+        #[clap(long, conflicts_with = "email")]
+        device: bool,
+        
+        /// Log in with a magic link or one-time code emailed to this address instead
+        #[clap(long, value_name = "EMAIL")]
+        email: Option<String>,
+        
+        #[clap(flatten)]
+        password: PasswordArgs,
+    },
     
     /// Show the current logged-in user
     Whoami,
+    
+    /// Log out, revoking the session and removing stored credentials
+    Logout {
+        /// Which sessions to revoke on the server
+        #[clap(long, value_enum, default_value = "global")]
+        scope: LogoutScope,
+    },
+    
+    /// Run as a Model Context Protocol server
+    Serve {
+        /// Serve over stdin/stdout
+        #[clap(long, conflicts_with = "http")]
+        stdio: bool,
+        
+        /// Serve streamable HTTP on this address, e.g. 127.0.0.1:8080
+        #[clap(long, value_name = "ADDR")]
+        http: Option<SocketAddr>,
+        
+        /// URL clients reach the HTTP server at, when it differs from the bind address
+        #[clap(long, requires = "http")]
+        public_url: Option<Url>,
+    },
+    
+    /// Talk to another MCP server with the stored session
+    Client {
+        #[clap(subcommand)]
+        command: ClientCommands,
+    },
+    
+    /// Expose a remote streamable HTTP MCP server over stdio, signed in with the stored session
+    Proxy {
+        /// URL of the remote MCP endpoint
+        url: Url,
+    },
+    
+    /// Query the project's database as the logged-in user, under row-level security
+    Db {
+        /// How to print rows
+        #[clap(long, short, value_enum, default_value = "table", global = true)]
+        output: OutputFormat,
+        
+        #[clap(subcommand)]
+        command: DbCommands,
+    },
+    
+    /// Manage files in Supabase Storage as the logged-in user, under storage policies
+    Storage {
+        #[clap(subcommand)]
+        command: StorageCommands,
+    },
+    
+    /// Call Supabase Edge Functions as the logged-in user
+    #[clap(name = "fn")]
+    Function {
+        #[clap(subcommand)]
+        command: FunctionCommands,
+    },
+    
+    /// Watch database changes, broadcasts and presence with Supabase Realtime
+    Realtime {
+        #[clap(subcommand)]
+        command: RealtimeCommands,
+    },
+    
+    /// Manage the sign-in identities linked to the account
+    Identity {
+        #[clap(subcommand)]
+        command: IdentityCommands,
+    },
+    
+    /// Inspect the stored access token
+    Token {
+        #[clap(subcommand)]
+        command: TokenCommands,
+    },
+    
+    /// Manage named profiles
+    Profile {
+        #[clap(subcommand)]
+        command: ProfileCommands,
+    },
+}
+
+/// How to read the password for `--email`
+#[derive(Args)]
+struct PasswordArgs {
+    /// Use the account's password, prompted for on the terminal
+    #[clap(long, requires = "email")]
+    password: bool,
+    
+    /// Read the password from the first line of stdin instead of prompting
+    #[clap(long, requires = "email", conflicts_with = "password")]
+    password_stdin: bool,
+}
+
+#[derive(Subcommand)]
+enum ClientCommands {
+    /// Connect to a server and show what it offers
+    Connect {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+    },
+    
+    /// List or call the server's tools
+    Tools {
+        #[clap(subcommand)]
+        command: ClientToolsCommands,
+    },
+    
+    /// List or read the server's resources
+    Resources {
+        #[clap(subcommand)]
+        command: ClientResourcesCommands,
+    },
+    
+    /// List or get the server's prompts
+    Prompts {
+        #[clap(subcommand)]
+        command: ClientPromptsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClientToolsCommands {
+    /// List the tools
+    List {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+    },
+    
+    /// Call a tool
+    Call {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+        
+        /// Name of the tool
+        name: String,
+        
+        /// Tool arguments as a JSON object
+        #[clap(long, default_value = "{}")]
+        args: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClientResourcesCommands {
+    /// List the resources
+    List {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+    },
+    
+    /// Read a resource
+    Read {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+        
+        /// URI of the resource
+        uri: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClientPromptsCommands {
+    /// List the prompts
+    List {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+    },
+    
+    /// Get a prompt filled in with arguments
+    Get {
+        /// Server URL, or a command line that starts a stdio server
+        target: String,
+        
+        /// Name of the prompt
+        name: String,
+        
+        /// Prompt arguments as a JSON object of strings
+        #[clap(long, default_value = "{}")]
+        args: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// List the tables and views the REST API exposes
+    Tables,
+    
+    /// Select rows from a table or view
+    Select {
+        /// Name of the table or view
+        table: String,
+        
+        /// Columns to return, in PostgREST select syntax
+        #[clap(long, default_value = "*")]
+        select: String,
+        
+        /// Keep rows matching a PostgREST condition, e.g. status=eq.active; repeatable
+        #[clap(long, value_name = "COLUMN=OP.VALUE")]
+        filter: Vec<String>,
+        
+        /// Sort by a column, e.g. created_at.desc; repeatable
+        #[clap(long, value_name = "COLUMN[.asc|.desc]")]
+        order: Vec<String>,
+        
+        /// Return at most this many rows
+        #[clap(long, default_value = "20", value_parser = clap::value_parser!(u64).range(1..))]
+        limit: u64,
+        
+        /// Skip this many rows first
+        #[clap(long, default_value = "0")]
+        offset: u64,
+        
+        /// Count all matching rows and report the total
+        #[clap(long)]
+        count: bool,
+    },
+    
+    /// Insert rows into a table
+    Insert {
+        /// Name of the table
+        table: String,
+        
+        /// File with a JSON object or array of objects, or - for stdin
+        #[clap(long, value_name = "FILE")]
+        json: String,
+        
+        /// Update rows whose key already exists instead of failing
+        #[clap(long)]
+        upsert: bool,
+        
+        /// Unique columns that decide whether an upserted row already exists
+        #[clap(long, requires = "upsert")]
+        on_conflict: Option<String>,
+    },
+    
+    /// Call a database function
+    Rpc {
+        /// Name of the function
+        function: String,
+        
+        /// Named arguments as a JSON object
+        #[clap(long, default_value = "{}")]
+        args: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum StorageCommands {
+    /// List buckets, or the objects and folders under supabase://bucket/prefix
+    Ls {
+        /// Folder to list, e.g. supabase://avatars/users/
+        path: Option<String>,
+    },
+    
+    /// Copy a local file to Storage, an object to a local file, or an object within Storage
+    Cp {
+        /// Local path or supabase://bucket/path
+        source: String,
+        
+        /// Local path or supabase://bucket/path; a trailing / keeps the source's file name
+        destination: String,
+        
+        /// Overwrite an existing object when uploading
+        #[clap(long)]
+        upsert: bool,
+    },
+    
+    /// Move or rename an object within Storage
+    Mv {
+        /// Object to move, as supabase://bucket/path
+        source: String,
+        
+        /// New location, as supabase://bucket/path
+        destination: String,
+    },
+    
+    /// Remove objects
+    Rm {
+        /// Objects to remove, as supabase://bucket/path
+        #[clap(required = true)]
+        paths: Vec<String>,
+    },
+    
+    /// Create a URL that grants temporary access to an object
+    Sign {
+        /// Object to share, as supabase://bucket/path
+        path: String,
+        
+        /// Seconds until the URL expires
+        #[clap(long, default_value = "3600")]
+        expires_in: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum FunctionCommands {
+    /// Invoke a function and stream its response to stdout
+    Invoke {
+        /// Name of the function
+        name: String,
+        
+        /// Request body: the body itself, @file, or @- for stdin
+        #[clap(long)]
+        data: Option<String>,
+        
+        /// Extra request header, e.g. 'X-Trace: abc', replacing any default of that name; repeatable
+        #[clap(long, short = 'H', value_name = "NAME: VALUE")]
+        header: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RealtimeCommands {
+    /// Join a channel and print its events as NDJSON until interrupted
+    Listen {
+        /// Channel to join
+        #[clap(long, default_value = "rust-mcp")]
+        channel: String,
+        
+        /// Watch changes to this table
+        #[clap(long)]
+        table: Option<String>,
+        
+        /// Schema of the watched tables; without --table, watches every table in it
+        #[clap(long)]
+        schema: Option<String>,
+        
+        /// Kind of change to watch
+        #[clap(long, default_value = "*", value_parser = ["*", "INSERT", "UPDATE", "DELETE"])]
+        event: String,
+        
+        /// Only changes to rows matching a PostgREST condition, e.g. room_id=eq.42
+        #[clap(long, requires = "table", value_name = "COLUMN=OP.VALUE")]
+        filter: Option<String>,
+        
+        /// Announce presence on the channel with this JSON state
+        #[clap(long, value_name = "JSON")]
+        track: Option<String>,
+        
+        /// Use a private channel, authorized by Realtime's RLS policies
+        #[clap(long)]
+        private: bool,
+    },
+    
+    /// Send one broadcast message to a channel
+    Broadcast {
+        /// Channel to send on
+        channel: String,
+        
+        /// Name of the broadcast event
+        event: String,
+        
+        /// Message payload as a JSON object
+        #[clap(long, default_value = "{}")]
+        payload: String,
+        
+        /// Use a private channel, authorized by Realtime's RLS policies
+        #[clap(long)]
+        private: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum IdentityCommands {
+    /// List the linked identities: ID, provider, email or username, and last sign-in
+    List,
+    
+    /// Link an identity from another provider, signing in with it in the browser
+    Link {
+        /// OAuth provider enabled on the project, e.g. gitlab or google
+        #[clap(long)]
+        provider: String,
+    },
+    
+    /// Unlink an identity; the account's last identity cannot be unlinked
+    Unlink {
+        /// ID of the identity, as shown by `identity list`
+        identity_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokenCommands {
+    /// Decode and validate the access token locally
+    Inspect {
+        /// Also verify the signature using the project's JWKS or the configured JWT secret
+        #[clap(long)]
+        verify: bool,
+        
+        /// Print the claims as JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// List the configured profiles
+    List,
+    
+    /// Add a profile, or update an existing one
+    Add {
+        /// Name of the profile
+        name: String,
+        
+        /// Supabase project URL
+        #[clap(long)]
+        supabase_url: String,
+        
+        /// Supabase anon key
+        #[clap(long)]
+        supabase_anon_key: String,
+        
+        /// GitHub OAuth app client ID
+        #[clap(long)]
+        github_client_id: String,
+        
+        /// Where to store the profile's session
+        #[clap(long, value_enum, default_value = "auto")]
+        credential_store: CredentialStoreKind,
+        
+        /// Project JWT secret for verifying HS256 access tokens locally
+        #[clap(long)]
+        jwt_secret: Option<String>,
+    },
+    
+    /// Make a profile the default
+    Use {
+        /// Name of the profile
+        name: String,
+    },
+    
+    /// Remove a profile and its stored credentials
+    Remove {
+        /// Name of the profile
+        name: String,
+    },
 }
 
 #[tokio::main]
@@ -39,8 +543,17 @@ async fn main() -> McpResult<()> {
     // Parse command line arguments
     let cli = Cli::parse();
     
+    // Profile management works on the config file directly
+    if let Commands::Profile { command } = cli.command {
+        if let Err(err) = run_profile_command(command) {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+    
     // Initialize environment and load config
-    let config = match init_environment() {
+    let config = match init_environment(cli.profile.as_deref()) {
         Ok(config) => config,
         Err(err) => {
             eprintln!("Error initializing environment: {}", err);
@@ -53,14 +566,40 @@ async fn main() -> McpResult<()> {
     
     // Process the command
     match cli.command {
-        Commands::Signup => {
-            if let Err(err) = auth_handler.signup().await {
+        Commands::Signup { provider, email, password } => {
+            let result = match email {
+                Some(email) => match read_password(&password, true) {
+                    Ok(password) => auth_handler.signup_with_password(&email, &password).await,
+                    Err(err) => Err(err),
+                },
+                None => match OAuthProvider::new(&provider, auth_handler.config()) {
+                    Ok(provider) => auth_handler.signup(&provider).await,
+                    Err(err) => Err(err),
+                },
+            };
+            
+            if let Err(err) = result {
                 eprintln!("Signup failed: {}", err);
                 process::exit(1);
             }
         }
-        Commands::Login => {
-            if let Err(err) = auth_handler.login().await {
+        Commands::Login { provider, device, email, password } => {
+            let result = match email {
+                Some(email) if password.password || password.password_stdin => {
+                    match read_password(&password, false) {
+                        Ok(password) => auth_handler.login_with_password(&email, &password).await,
+                        Err(err) => Err(err),
+                    }
+                }
+                Some(email) => auth_handler.login_with_email(&email).await,
+                None if device => auth_handler.login_with_device().await,
+                None => match OAuthProvider::new(&provider, auth_handler.config()) {
+                    Ok(provider) => auth_handler.login(&provider).await,
+                    Err(err) => Err(err),
+                },
+            };
+            
+            if let Err(err) = result {
                 eprintln!("Login failed: {}", err);
                 process::exit(1);
             }
@@ -71,6 +610,551 @@ async fn main() -> McpResult<()> {
                 process::exit(1);
             }
         }
+        Commands::Logout { scope } => {
+            if let Err(err) = auth_handler.logout(scope).await {
+                eprintln!("Logout failed: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Serve { stdio, http, public_url } => {
+            let server = match McpServer::new(auth_handler.config()) {
+                Ok(server) => Arc::new(server),
+                Err(err) => {
+                    eprintln!("Server error: {}", err);
+                    process::exit(1);
+                }
+            };
+            let auth_handler = Arc::new(auth_handler);
+            
+            let result = match http {
+                Some(addr) => serve_http(server, auth_handler, addr, public_url).await,
+                None if stdio => serve_stdio(server, auth_handler).await,
+                None => {
+                    eprintln!("Error: choose a transport, --stdio or --http <ADDR>");
+                    process::exit(1);
+                }
+            };
+            
+            if let Err(err) = result {
+                eprintln!("Server error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Client { command } => {
+            if let Err(err) = run_client_command(command, Arc::new(auth_handler)).await {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Proxy { url } => {
+            if let Err(err) = run_proxy(url, Arc::new(auth_handler)).await {
+                eprintln!("Proxy error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Db { output, command } => {
+            if let Err(err) = run_db_command(command, output, &auth_handler).await {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Storage { command } => {
+            if let Err(err) = run_storage_command(command, &auth_handler).await {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Function { command: FunctionCommands::Invoke { name, data, header } } => {
+            if let Err(err) = invoke_function(&auth_handler, &name, data, &header).await {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Realtime { command } => {
+            if let Err(err) = run_realtime_command(command, &auth_handler).await {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Identity { command } => {
+            let result = match command {
+                IdentityCommands::List => auth_handler.list_identities().await,
+                IdentityCommands::Link { provider } => match OAuthProvider::new(&provider, auth_handler.config()) {
+                    Ok(provider) => auth_handler.link_identity(&provider).await,
+                    Err(err) => Err(err),
+                },
+                IdentityCommands::Unlink { identity_id } => auth_handler.unlink_identity(&identity_id).await,
+            };
+            
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Token { command: TokenCommands::Inspect { verify, json } } => {
+            if let Err(err) = auth_handler.inspect_token(verify, json).await {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        Commands::Profile { .. } => unreachable!("handled above"),
+    }
+    
+    Ok(())
+}
+
+/// Connect to the target server, run one client command and disconnect
+async fn run_client_command(command: ClientCommands, auth: Arc<AuthHandler>) -> McpResult<()> {
+    let target = match &command {
+        ClientCommands::Connect { target }
+        | ClientCommands::Tools { command: ClientToolsCommands::List { target } }
+        | ClientCommands::Tools { command: ClientToolsCommands::Call { target, .. } }
+        | ClientCommands::Resources { command: ClientResourcesCommands::List { target } }
+        | ClientCommands::Resources { command: ClientResourcesCommands::Read { target, .. } }
+        | ClientCommands::Prompts { command: ClientPromptsCommands::List { target } }
+        | ClientCommands::Prompts { command: ClientPromptsCommands::Get { target, .. } } => target,
+    };
+    
+    let client = McpClient::connect(target, auth).await?;
+    
+    // Close the connection whether or not the command succeeded
+    let result = async {
+        match command {
+            ClientCommands::Connect { .. } => {
+                print_server_info(client.server());
+                Ok(())
+            }
+            ClientCommands::Tools { command: ClientToolsCommands::List { .. } } => {
+                let result = client.request("tools/list", serde_json::json!({})).await?;
+                print_list(&result, "tools", "name");
+                Ok(())
+            }
+            ClientCommands::Tools { command: ClientToolsCommands::Call { name, args, .. } } => {
+                let arguments = parse_json_object("--args", &args)?;
+                let result = client
+                    .request("tools/call", serde_json::json!({ "name": name, "arguments": arguments }))
+                    .await?;
+                print_tool_result(&result)
+            }
+            ClientCommands::Resources { command: ClientResourcesCommands::List { .. } } => {
+                let result = client.request("resources/list", serde_json::json!({})).await?;
+                print_list(&result, "resources", "uri");
+                Ok(())
+            }
+            ClientCommands::Resources { command: ClientResourcesCommands::Read { uri, .. } } => {
+                let result = client.request("resources/read", serde_json::json!({ "uri": uri })).await?;
+                print_resource_contents(&result);
+                Ok(())
+            }
+            ClientCommands::Prompts { command: ClientPromptsCommands::List { .. } } => {
+                let result = client.request("prompts/list", serde_json::json!({})).await?;
+                print_list(&result, "prompts", "name");
+                Ok(())
+            }
+            ClientCommands::Prompts { command: ClientPromptsCommands::Get { name, args, .. } } => {
+                let arguments = parse_json_object("--args", &args)?;
+                let result = client
+                    .request("prompts/get", serde_json::json!({ "name": name, "arguments": arguments }))
+                    .await?;
+                print_prompt(&result);
+                Ok(())
+            }
+        }
+    }
+    .await;
+    
+    client.close().await?;
+    result
+}
+
+/// Read a password from stdin or the terminal, asking twice when `confirm` is set
+fn read_password(args: &PasswordArgs, confirm: bool) -> McpResult<String> {
+    let password = if args.password_stdin {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    } else {
+        let prompt = |prompt| {
+            rpassword::prompt_password(prompt).map_err(|e| {
+                McpError::InvalidArguments(format!("Cannot prompt for a password ({}); use --password-stdin", e))
+            })
+        };
+        
+        let password = prompt("Password: ")?;
+        if confirm && prompt("Confirm password: ")? != password {
+            return Err(McpError::InvalidArguments("Passwords do not match".to_string()));
+        }
+        password
+    };
+    
+    if password.is_empty() {
+        return Err(McpError::InvalidArguments("Password must not be empty".to_string()));
+    }
+    
+    Ok(password)
+}
+
+/// Parse a flag value that must be a JSON object
+fn parse_json_object(flag: &str, value: &str) -> McpResult<serde_json::Value> {
+    let value: serde_json::Value = serde_json::from_str(value)
+        .map_err(|e| McpError::InvalidArguments(format!("{} is not valid JSON: {}", flag, e)))?;
+    
+    if !value.is_object() {
+        return Err(McpError::InvalidArguments(format!("{} must be a JSON object", flag)));
+    }
+    
+    Ok(value)
+}
+
+/// Run a `db` subcommand with the stored session
+async fn run_db_command(command: DbCommands, output: OutputFormat, auth: &AuthHandler) -> McpResult<()> {
+    let token = auth.get_token().await?;
+    let supabase = auth.supabase();
+    
+    match command {
+        DbCommands::Tables => {
+            let tables: Vec<serde_json::Value> = supabase
+                .list_tables(&token)
+                .await?
+                .into_iter()
+                .map(|table| serde_json::json!({ "name": table.name, "description": table.description }))
+                .collect();
+            print_rows(&serde_json::Value::Array(tables), output)
+        }
+        DbCommands::Select { table, select, filter, order, limit, offset, count } => {
+            let mut query = supabase.from(&table).select(&select);
+            for filter in &filter {
+                let (column, condition) = parse_filter(filter)?;
+                query = query.filter(&column, &condition);
+            }
+            for order in &order {
+                let (column, ascending) = parse_order(order);
+                query = query.order(&column, ascending);
+            }
+            let (first, last) = row_range(offset, limit)?;
+            query = query.range(first, last);
+            if count {
+                query = query.exact_count();
+            }
+            
+            let response = query.execute::<serde_json::Value>(&token).await?;
+            print_rows(&response.data, output)?;
+            if count {
+                print_content_range(response.content_range);
+            }
+            Ok(())
+        }
+        DbCommands::Insert { table, json, upsert, on_conflict } => {
+            let rows = read_rows(&json)?;
+            
+            let mut query = if upsert {
+                supabase.from(&table).upsert(&rows)?
+            } else {
+                supabase.from(&table).insert(&rows)?
+            };
+            if let Some(columns) = on_conflict {
+                query = query.on_conflict(&columns);
+            }
+            
+            let response = query
+                .return_representation()
+                .execute::<serde_json::Value>(&token)
+                .await?;
+            print_rows(&response.data, output)
+        }
+        DbCommands::Rpc { function, args } => {
+            let args = parse_json_object("--args", &args)?;
+            let response = supabase
+                .rpc(&function, &args)?
+                .execute::<serde_json::Value>(&token)
+                .await?;
+            print_rows(&response.data, output)
+        }
+    }
+}
+
+/// Run a `storage` subcommand with the stored session
+async fn run_storage_command(command: StorageCommands, auth: &AuthHandler) -> McpResult<()> {
+    let token = auth.get_token().await?;
+    let supabase = auth.supabase();
+    
+    match command {
+        StorageCommands::Ls { path: None } => {
+            for bucket in supabase.list_buckets(&token).await? {
+                let visibility = if bucket.public { "public" } else { "private" };
+                println!("{}\t{}", bucket.name, visibility);
+            }
+        }
+        StorageCommands::Ls { path: Some(path) } => {
+            let folder = parse_object_path(&path)?;
+            
+            // Storage pages listings; keep fetching until a short page
+            const PAGE_SIZE: usize = 100;
+            let mut offset = 0;
+            loop {
+                let page = supabase
+                    .list_objects(&token, &folder.bucket, &folder.path, PAGE_SIZE, offset)
+                    .await?;
+                for object in &page {
+                    if object.is_folder() {
+                        println!("{:>12}\t{:<24}\t{}/", "", "", object.name);
+                    } else {
+                        let size = object.size().map(|size| size.to_string()).unwrap_or_default();
+                        let updated_at = object.updated_at.as_deref().unwrap_or_default();
+                        println!("{:>12}\t{:<24}\t{}", size, updated_at, object.name);
+                    }
+                }
+                
+                if page.len() < PAGE_SIZE {
+                    break;
+                }
+                offset += page.len();
+            }
+        }
+        StorageCommands::Cp { source, destination, upsert } => {
+            let (source_object, destination_object) =
+                (ObjectPath::parse(&source)?, ObjectPath::parse(&destination)?);
+            
+            // Downloads overwrite local files, and Storage cannot overwrite on copy
+            if upsert && source_object.is_some() {
+                return Err(McpError::InvalidArguments(
+                    "--upsert only applies to uploads from a local file".to_string(),
+                ));
+            }
+            
+            match (source_object, destination_object) {
+                (None, Some(destination)) => {
+                    let object = remote_destination(Path::new(&source), &destination)?;
+                    let size = supabase
+                        .upload_object(&token, &object, Path::new(&source), upsert)
+                        .await?;
+                    println!("Uploaded {} ({} bytes)", object, size);
+                }
+                (Some(object), None) => {
+                    let file = local_destination(&object, Path::new(&destination))?;
+                    let size = supabase.download_object(&token, &object, &file).await?;
+                    println!("Downloaded {} to {} ({} bytes)", object, file.display(), size);
+                }
+                (Some(from), Some(to)) => {
+                    let to = remote_destination(Path::new(&from.path), &to)?;
+                    supabase.copy_object(&token, &from, &to).await?;
+                    println!("Copied {} to {}", from, to);
+                }
+                (None, None) => {
+                    return Err(McpError::InvalidArguments(
+                        "One side of cp must be a supabase://bucket/path".to_string(),
+                    ));
+                }
+            }
+        }
+        StorageCommands::Mv { source, destination } => {
+            let from = parse_object_path(&source)?;
+            let to = remote_destination(Path::new(&from.path), &parse_object_path(&destination)?)?;
+            supabase.move_object(&token, &from, &to).await?;
+            println!("Moved {} to {}", from, to);
+        }
+        StorageCommands::Rm { paths } => {
+            let objects = paths
+                .iter()
+                .map(|path| parse_object_path(path))
+                .collect::<McpResult<Vec<_>>>()?;
+            
+            // One request per bucket
+            let mut buckets: Vec<&str> = Vec::new();
+            for object in &objects {
+                if !buckets.contains(&object.bucket.as_str()) {
+                    buckets.push(&object.bucket);
+                }
+            }
+            
+            let mut missing = 0;
+            for bucket in buckets {
+                let keys: Vec<String> = objects
+                    .iter()
+                    .filter(|object| object.bucket == bucket)
+                    .map(|object| object.path.clone())
+                    .collect();
+                
+                let removed = supabase.remove_objects(&token, bucket, &keys).await?;
+                for key in &keys {
+                    if removed.iter().any(|object| object.name == *key) {
+                        println!("Removed supabase://{}/{}", bucket, key);
+                    } else {
+                        eprintln!("Not found: supabase://{}/{}", bucket, key);
+                        missing += 1;
+                    }
+                }
+            }
+            
+            if missing > 0 {
+                return Err(McpError::StorageError(format!("{} object(s) not found", missing)));
+            }
+        }
+        StorageCommands::Sign { path, expires_in } => {
+            let object = parse_object_path(&path)?;
+            println!("{}", supabase.create_signed_url(&token, &object, expires_in).await?);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Invoke an Edge Function and copy its response to stdout as it arrives
+async fn invoke_function(
+    auth: &AuthHandler,
+    name: &str,
+    data: Option<String>,
+    headers: &[String],
+) -> McpResult<()> {
+    let body = data.as_deref().map(read_data).transpose()?;
+    
+    let mut headers = headers
+        .iter()
+        .map(|header| parse_header(header))
+        .collect::<McpResult<Vec<_>>>()?;
+    if let Some(body) = &body
+        && !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        headers.push(("Content-Type".to_string(), content_type_of(body).to_string()));
+    }
+    
+    let token = auth.get_token().await?;
+    let mut response = auth.supabase().invoke_function(&token, name, body, &headers).await?;
+    
+    let mut stdout = tokio::io::stdout();
+    while let Some(chunk) = response.chunk().await? {
+        stdout.write_all(&chunk).await?;
+        stdout.flush().await?;
+    }
+    
+    Ok(())
+}
+
+/// Run a `realtime` subcommand with the stored session
+async fn run_realtime_command(command: RealtimeCommands, auth: &AuthHandler) -> McpResult<()> {
+    // How often to check whether the access token was refreshed
+    const TOKEN_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+    
+    let token = auth.get_token().await?;
+    
+    match command {
+        RealtimeCommands::Listen { channel, table, schema, event, filter, track, private } => {
+            let mut options = ChannelOptions { private, ..Default::default() };
+            if table.is_some() || schema.is_some() {
+                options.postgres_changes.push(PostgresChangesFilter {
+                    event,
+                    schema: schema.unwrap_or_else(|| "public".to_string()),
+                    table,
+                    filter,
+                });
+            }
+            let track = track.map(|track| parse_json_object("--track", &track)).transpose()?;
+            
+            let mut client = auth.supabase().realtime(&token).await?;
+            client.join(&channel, &options).await?;
+            if let Some(state) = track {
+                client.track(&channel, state)?;
+            }
+            eprintln!("Listening on {}; press Ctrl-C to stop.", channel);
+            
+            let mut token_check = tokio::time::interval(TOKEN_CHECK_INTERVAL);
+            loop {
+                tokio::select! {
+                    event = client.next_event() => match event? {
+                        RealtimeEvent::System { payload, .. } => {
+                            eprintln!("{}", payload["message"].as_str().unwrap_or_default());
+                        }
+                        RealtimeEvent::ChannelClosed { channel, reason } => {
+                            return Err(McpError::RealtimeError(format!("{} {}", channel, reason)));
+                        }
+                        event => println!("{}", serde_json::to_string(&event)?),
+                    },
+                    _ = token_check.tick() => {
+                        // Refreshes the session when it is about to expire
+                        let token = auth.get_token().await?;
+                        client.set_access_token(&token)?;
+                    }
+                    _ = tokio::signal::ctrl_c() => break,
+                }
+            }
+            
+            let _ = client.leave(&channel).await;
+            client.close().await;
+        }
+        RealtimeCommands::Broadcast { channel, event, payload, private } => {
+            let payload = parse_json_object("--payload", &payload)?;
+            
+            let client = auth.supabase().realtime(&token).await?;
+            client.join(&channel, &ChannelOptions { private, ..Default::default() }).await?;
+            client.broadcast(&channel, &event, payload)?;
+            client.leave(&channel).await?;
+            client.close().await;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Parse an argument that must name a Storage location
+fn parse_object_path(path: &str) -> McpResult<ObjectPath> {
+    ObjectPath::parse(path)?.ok_or_else(|| {
+        McpError::InvalidArguments(format!("{} is not a supabase://bucket/path", path))
+    })
+}
+
+/// Run a `profile` subcommand
+fn run_profile_command(command: ProfileCommands) -> McpResult<()> {
+    let mut config_file = ConfigFile::load()?;
+    
+    match command {
+        ProfileCommands::List => {
+            for (name, profile) in &config_file.profiles {
+                let marker = if *name == config_file.default_profile { "*" } else { " " };
+                println!("{} {} ({})", marker, name, profile.supabase_url);
+            }
+        }
+        ProfileCommands::Add {
+            name,
+            supabase_url,
+            supabase_anon_key,
+            github_client_id,
+            credential_store,
+            jwt_secret,
+        } => {
+            // Settings only edited in the config file, like function tools, are kept when
+            // updating a profile
+            let existing = config_file.profiles.get(&name).cloned().unwrap_or_default();
+            
+            let config = Config {
+                profile: name.clone(),
+                supabase_url,
+                supabase_anon_key,
+                github_client_id,
+                credential_store,
+                jwt_secret,
+                ..existing
+            };
+            config_file.add_profile(&name, config);
+            config_file.save()?;
+            println!("Saved profile '{}'.", name);
+        }
+        ProfileCommands::Use { name } => {
+            config_file.set_default(&name)?;
+            config_file.save()?;
+            println!("Default profile is now '{}'.", name);
+        }
+        ProfileCommands::Remove { name } => {
+            let config = config_file.remove_profile(&name)?;
+            config_file.save()?;
+            
+            match AuthHandler::new(config).delete_session() {
+                Ok(()) => println!("Removed profile '{}' and its stored credentials.", name),
+                Err(err) => println!(
+                    "Removed profile '{}', but could not delete its stored credentials: {}",
+                    name, err
+                ),
+            }
+        }
     }
     
     Ok(())
@@ -0,0 +1,709 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::{Value, json};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use url::{Host, Url};
+
+use crate::auth::AuthHandler;
+use crate::discovery::ProtectedResourceMetadata;
+use crate::error::{McpError, McpResult, to_other_error};
+use crate::mcp::protocol::{
+    JSONRPC_VERSION, JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest,
+    METHOD_NOT_FOUND, RequestId, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use crate::mcp::server::SERVER_NAME;
+
+/// Header carrying the ID of the MCP session a request belongs to
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the negotiated protocol revision on HTTP requests after `initialize`
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// How long a spawned server gets to exit after its stdin is closed
+const CHILD_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A way of exchanging JSON-RPC messages with an MCP server
+#[async_trait]
+pub trait ClientTransport: Send + Sync {
+    /// Send a message and wait for the response if it is a request
+    async fn send(&self, message: JsonRpcMessage) -> McpResult<Option<JsonRpcMessage>>;
+
+    /// Tell the server the connection is over
+    async fn close(&self) -> McpResult<()>;
+}
+
+/// A connection to an MCP server, past the `initialize` handshake
+pub struct McpClient {
+    transport: Box<dyn ClientTransport>,
+    next_id: AtomicI64,
+    server: Value,
+}
+
+impl McpClient {
+    /// Connect to `target` and perform the handshake
+    ///
+    /// `http://` and `https://` targets use streamable HTTP with the profile's access token;
+    /// anything else is a command line to spawn and talk to over stdio.
+    pub async fn connect(target: &str, auth: Arc<AuthHandler>) -> McpResult<Self> {
+        let transport: Box<dyn ClientTransport> = match Url::parse(target) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                Box::new(HttpTransport::connect(url, auth).await?)
+            }
+            _ => Box::new(StdioTransport::spawn(target)?),
+        };
+
+        let mut client = Self {
+            transport,
+            next_id: AtomicI64::new(1),
+            server: Value::Null,
+        };
+        client.initialize().await?;
+
+        Ok(client)
+    }
+
+    async fn initialize(&mut self) -> McpResult<()> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": SUPPORTED_PROTOCOL_VERSIONS[0],
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": SERVER_NAME,
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+
+        let version = result["protocolVersion"].as_str().unwrap_or_default();
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return Err(McpError::RemoteError(format!(
+                "Server wants unsupported protocol version '{}'",
+                version
+            )));
+        }
+
+        self.transport
+            .send(JsonRpcMessage::Notification(JsonRpcNotification {
+                jsonrpc: JSONRPC_VERSION.to_string(),
+                method: "notifications/initialized".to_string(),
+                params: None,
+            }))
+            .await?;
+
+        self.server = result;
+        Ok(())
+    }
+
+    /// The server's `initialize` result: protocol version, capabilities and server info
+    pub fn server(&self) -> &Value {
+        &self.server
+    }
+
+    /// Send a request and return its result
+    pub async fn request(&self, method: &str, params: Value) -> McpResult<Value> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+
+        let request = JsonRpcMessage::Request(JsonRpcRequest {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            method: method.to_string(),
+            params: Some(params),
+        });
+
+        match self.transport.send(request).await? {
+            Some(JsonRpcMessage::Response(response)) => Ok(response.result),
+            Some(JsonRpcMessage::Error(response)) => Err(McpError::RemoteError(format!(
+                "{} (code {})",
+                response.error.message, response.error.code
+            ))),
+            _ => Err(McpError::RemoteError(format!("No response to {}", method))),
+        }
+    }
+
+    /// End the connection
+    pub async fn close(self) -> McpResult<()> {
+        self.transport.close().await
+    }
+}
+
+/// Streamable HTTP transport that authenticates with the profile's access token
+pub struct HttpTransport {
+    client: Client,
+    url: Url,
+    auth: Arc<AuthHandler>,
+    token: Mutex<Option<String>>,
+    session_id: Mutex<Option<String>>,
+    protocol_version: Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    /// Prepare a transport for the server at `url`
+    ///
+    /// Without a login, or when the server is not one to trust with the token, requests are
+    /// sent unauthenticated so servers that need no auth still work.
+    pub async fn connect(url: Url, auth: Arc<AuthHandler>) -> McpResult<Self> {
+        let client = Client::new();
+
+        let token = match auth.get_token().await {
+            Ok(token) => {
+                let issuer = auth.config().auth_issuer();
+                match check_token_audience(&client, &url, &issuer).await {
+                    Ok(()) => Some(token),
+                    Err(reason) => {
+                        eprintln!("Not sending the access token to {}: {}", url, reason);
+                        None
+                    }
+                }
+            }
+            Err(McpError::NotAuthenticated) => None,
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            client,
+            url,
+            auth,
+            token: Mutex::new(token),
+            session_id: Mutex::new(None),
+            protocol_version: Mutex::new(None),
+        })
+    }
+
+    /// POST a message to the server
+    pub async fn post(&self, message: &JsonRpcMessage) -> McpResult<Response> {
+        let response = self
+            .execute(|| {
+                self.client
+                    .post(self.url.clone())
+                    .header(ACCEPT, "application/json, text/event-stream")
+                    .json(message)
+            })
+            .await?;
+
+        self.check_status(response)
+    }
+
+    /// Open the stream of server-initiated messages, if the server offers one
+    pub async fn listen(&self) -> McpResult<Option<Response>> {
+        let response = self
+            .execute(|| {
+                self.client
+                    .get(self.url.clone())
+                    .header(ACCEPT, "text/event-stream")
+            })
+            .await?;
+
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(None);
+        }
+
+        self.check_status(response).map(Some)
+    }
+
+    /// Pass each JSON-RPC message in a response body to `handle` until it returns `true`
+    ///
+    /// Handles both plain JSON bodies and Server-Sent Events streams, which are read as they
+    /// arrive.
+    pub async fn read_messages(
+        &self,
+        response: Response,
+        mut handle: impl FnMut(JsonRpcMessage) -> bool,
+    ) -> McpResult<()> {
+        if response.status() == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let is_event_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+
+        if !is_event_stream {
+            let body = response.text().await.map_err(McpError::HttpError)?;
+            if let Ok(message) = JsonRpcMessage::parse(&body) {
+                self.observe(&message);
+                handle(message);
+            }
+            return Ok(());
+        }
+
+        let mut decoder = SseDecoder::default();
+        let mut chunks = response.bytes_stream();
+
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(McpError::HttpError)?;
+
+            for data in decoder.feed(&chunk) {
+                let Ok(message) = JsonRpcMessage::parse(&data) else {
+                    continue;
+                };
+                self.observe(&message);
+                if handle(message) {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tell the server the session is over
+    pub async fn close(&self) -> McpResult<()> {
+        if self.session_id.lock().unwrap().is_none() {
+            return Ok(());
+        }
+
+        // Servers may not support ending sessions explicitly, which is fine
+        let _ = self.execute(|| self.client.delete(self.url.clone())).await;
+
+        Ok(())
+    }
+
+    /// Send a request, refreshing the access token and retrying once if the server rejects it
+    async fn execute(&self, build: impl Fn() -> RequestBuilder) -> McpResult<Response> {
+        let mut response = self
+            .authorize(build())
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        let rejected = self.token.lock().unwrap().clone();
+        if response.status() == StatusCode::UNAUTHORIZED
+            && let Some(rejected) = rejected
+        {
+            let token = self.auth.refresh_rejected_token(&rejected).await?;
+            *self.token.lock().unwrap() = Some(token);
+
+            response = self
+                .authorize(build())
+                .send()
+                .await
+                .map_err(McpError::HttpError)?;
+        }
+
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            *self.session_id.lock().unwrap() = Some(session_id.to_string());
+        }
+
+        Ok(response)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let mut request = request;
+
+        if let Some(token) = self.token.lock().unwrap().as_deref() {
+            request = request.bearer_auth(token);
+        }
+        if let Some(session_id) = self.session_id.lock().unwrap().as_deref() {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        if let Some(version) = self.protocol_version.lock().unwrap().as_deref() {
+            request = request.header(PROTOCOL_VERSION_HEADER, version);
+        }
+
+        request
+    }
+
+    fn check_status(&self, response: Response) -> McpResult<Response> {
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(McpError::InvalidToken(
+                "The MCP server rejected the access token (HTTP 401)".to_string(),
+            )),
+            StatusCode::NOT_FOUND if self.session_id.lock().unwrap().is_some() => Err(
+                McpError::RemoteError("The MCP session has expired".to_string()),
+            ),
+            status if !status.is_success() => Err(McpError::RemoteError(format!(
+                "MCP request failed: HTTP {}",
+                status
+            ))),
+            _ => Ok(response),
+        }
+    }
+
+    /// Remember the protocol revision from an `initialize` result; later requests must send it
+    fn observe(&self, message: &JsonRpcMessage) {
+        if let JsonRpcMessage::Response(response) = message
+            && response.result.get("serverInfo").is_some()
+            && let Some(version) = response
+                .result
+                .get("protocolVersion")
+                .and_then(Value::as_str)
+        {
+            *self.protocol_version.lock().unwrap() = Some(version.to_string());
+        }
+    }
+}
+
+#[async_trait]
+impl ClientTransport for HttpTransport {
+    async fn send(&self, message: JsonRpcMessage) -> McpResult<Option<JsonRpcMessage>> {
+        let response = self.post(&message).await?;
+
+        let JsonRpcMessage::Request(request) = message else {
+            return Ok(None);
+        };
+
+        // The server may send notifications ahead of the response on the same stream
+        let mut reply = None;
+        self.read_messages(response, |message| {
+            let answers = response_id(&message) == Some(&request.id);
+            if answers {
+                reply = Some(message);
+            }
+            answers
+        })
+        .await?;
+
+        reply
+            .map(Some)
+            .ok_or_else(|| McpError::RemoteError("The MCP server sent no response".to_string()))
+    }
+
+    async fn close(&self) -> McpResult<()> {
+        HttpTransport::close(self).await
+    }
+}
+
+/// Check that the server at `url` may be sent the profile's access token
+///
+/// Supabase accepts the token from whoever holds it, so it only goes over TLS or loopback, to
+/// servers whose protected-resource metadata names the project's auth server.
+async fn check_token_audience(client: &Client, url: &Url, issuer: &str) -> Result<(), String> {
+    if url.scheme() != "https" && !is_loopback(url) {
+        return Err("it is a remote host reached over plain HTTP".to_string());
+    }
+
+    let metadata = ProtectedResourceMetadata::discover(client, url)
+        .await
+        .ok()
+        .flatten()
+        .ok_or_else(|| "it publishes no protected-resource metadata".to_string())?;
+
+    if !metadata.names_authorization_server(issuer) {
+        return Err(format!(
+            "its metadata does not name {} as an authorization server",
+            issuer
+        ));
+    }
+
+    Ok(())
+}
+
+fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
+/// Transport to a server spawned as a child process
+struct StdioTransport {
+    child: tokio::sync::Mutex<Child>,
+    stdin: tokio::sync::Mutex<Option<ChildStdin>>,
+    stdout: tokio::sync::Mutex<Lines<BufReader<ChildStdout>>>,
+}
+
+impl StdioTransport {
+    /// Spawn `command`, split on whitespace into program and arguments
+    fn spawn(command: &str) -> McpResult<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| McpError::ConfigError("Empty server command".to_string()))?;
+
+        // The server's stderr is its log, so it goes straight to ours
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| to_other_error("No stdin on child"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| to_other_error("No stdout on child"))?;
+
+        Ok(Self {
+            child: tokio::sync::Mutex::new(child),
+            stdin: tokio::sync::Mutex::new(Some(stdin)),
+            stdout: tokio::sync::Mutex::new(BufReader::new(stdout).lines()),
+        })
+    }
+
+    async fn write(&self, message: &JsonRpcMessage) -> McpResult<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+
+        let mut stdin = self.stdin.lock().await;
+        let stdin = stdin
+            .as_mut()
+            .ok_or_else(|| McpError::RemoteError("The connection is closed".to_string()))?;
+        stdin.write_all(line.as_bytes()).await?;
+        stdin.flush().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ClientTransport for StdioTransport {
+    async fn send(&self, message: JsonRpcMessage) -> McpResult<Option<JsonRpcMessage>> {
+        let request_id = match &message {
+            JsonRpcMessage::Request(request) => request.id.clone(),
+            _ => return self.write(&message).await.map(|_| None),
+        };
+
+        // Hold the reader for the whole exchange so responses cannot be taken by another call
+        let mut stdout = self.stdout.lock().await;
+        self.write(&message).await?;
+
+        while let Some(line) = stdout.next_line().await? {
+            let Ok(incoming) = JsonRpcMessage::parse(&line) else {
+                continue;
+            };
+
+            match incoming {
+                ref reply if response_id(reply) == Some(&request_id) => return Ok(Some(incoming)),
+                // This client offers no capabilities, so it can only answer pings
+                JsonRpcMessage::Request(request) => {
+                    let reply = if request.method == "ping" {
+                        JsonRpcMessage::response(request.id, json!({}))
+                    } else {
+                        JsonRpcMessage::error(
+                            Some(request.id),
+                            JsonRpcError::new(METHOD_NOT_FOUND, "Method not found"),
+                        )
+                    };
+                    self.write(&reply).await?;
+                }
+                _ => {}
+            }
+        }
+
+        Err(McpError::RemoteError("The MCP server exited".to_string()))
+    }
+
+    async fn close(&self) -> McpResult<()> {
+        // Closing stdin asks the server to exit; stop it if it does not
+        self.stdin.lock().await.take();
+
+        let mut child = self.child.lock().await;
+        if tokio::time::timeout(CHILD_EXIT_TIMEOUT, child.wait())
+            .await
+            .is_err()
+        {
+            child.kill().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// ID of the request a response or error answers
+pub fn response_id(message: &JsonRpcMessage) -> Option<&RequestId> {
+    match message {
+        JsonRpcMessage::Response(response) => Some(&response.id),
+        JsonRpcMessage::Error(response) => response.id.as_ref(),
+        _ => None,
+    }
+}
+
+/// Incremental parser for Server-Sent Events, yielding the data of each complete event
+#[derive(Default)]
+struct SseDecoder {
+    buffer: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    /// Add bytes from the stream and return the data of the events they complete
+    fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                // A blank line ends the event
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data
+                    .push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+        }
+
+        events
+    }
+}
+
+/// Print the server's name, protocol version and capabilities
+pub fn print_server_info(server: &Value) {
+    let info = &server["serverInfo"];
+    println!(
+        "Connected to {} {}",
+        info["name"].as_str().unwrap_or("unknown server"),
+        info["version"].as_str().unwrap_or_default()
+    );
+    println!(
+        "Protocol version: {}",
+        server["protocolVersion"].as_str().unwrap_or_default()
+    );
+
+    if let Some(capabilities) = server["capabilities"].as_object() {
+        let names: Vec<&str> = capabilities.keys().map(String::as_str).collect();
+        println!("Capabilities: {}", names.join(", "));
+    }
+    if let Some(instructions) = server["instructions"].as_str() {
+        println!();
+        println!("{}", instructions);
+    }
+}
+
+/// Print one line per item of a list result: its key and description
+pub fn print_list(result: &Value, field: &str, key: &str) {
+    for item in result[field].as_array().into_iter().flatten() {
+        let name = item[key].as_str().unwrap_or_default();
+        match item["description"].as_str() {
+            Some(description) => println!("{}\t{}", name, description),
+            None => println!("{}", name),
+        }
+    }
+}
+
+/// Print the content of a tool result, failing if the tool reported an error
+pub fn print_tool_result(result: &Value) -> McpResult<()> {
+    let text = content_text(&result["content"]);
+
+    if result["isError"].as_bool().unwrap_or(false) {
+        return Err(McpError::RemoteError(text));
+    }
+
+    if text.is_empty() && !result["structuredContent"].is_null() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&result["structuredContent"])?
+        );
+    } else {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+/// Print the contents returned by `resources/read`
+pub fn print_resource_contents(result: &Value) {
+    for contents in result["contents"].as_array().into_iter().flatten() {
+        println!("{}", embedded_resource_text(contents));
+    }
+}
+
+/// Print the messages returned by `prompts/get`
+pub fn print_prompt(result: &Value) {
+    if let Some(description) = result["description"].as_str() {
+        println!("{}", description);
+        println!();
+    }
+    for message in result["messages"].as_array().into_iter().flatten() {
+        println!("[{}]", message["role"].as_str().unwrap_or_default());
+        println!(
+            "{}",
+            content_text(&Value::Array(vec![message["content"].clone()]))
+        );
+    }
+}
+
+/// Text of a list of content blocks, with placeholders for binary content
+fn content_text(content: &Value) -> String {
+    content
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str().unwrap_or_default().to_string(),
+            Some("resource") => embedded_resource_text(&block["resource"]),
+            Some("resource_link") => format!("<{}>", block["uri"].as_str().unwrap_or_default()),
+            Some(kind) => format!(
+                "[{} content, {}]",
+                kind,
+                block["mimeType"].as_str().unwrap_or("unknown type")
+            ),
+            None => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn embedded_resource_text(resource: &Value) -> String {
+    match resource["text"].as_str() {
+        Some(text) => text.to_string(),
+        None => format!(
+            "[binary {} at {}]",
+            resource["mimeType"].as_str().unwrap_or("content"),
+            resource["uri"].as_str().unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_decoder_joins_data_lines_across_chunks() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.feed(b"event: message\ndata: {\"a\"").is_empty());
+        assert!(decoder.feed(b":1}\r\ndata:second\n").is_empty());
+        assert_eq!(decoder.feed(b"\n\n"), ["{\"a\":1}\nsecond"]);
+        assert!(decoder.feed(b": comment\n\n").is_empty());
+    }
+
+    #[test]
+    fn loopback_hosts() {
+        for url in [
+            "http://localhost:8080/mcp",
+            "http://127.0.0.1/mcp",
+            "http://[::1]/mcp",
+        ] {
+            assert!(is_loopback(&Url::parse(url).unwrap()), "{}", url);
+        }
+        for url in ["http://10.0.0.1/mcp", "http://localhost.example.com/mcp"] {
+            assert!(!is_loopback(&Url::parse(url).unwrap()), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn token_is_withheld_from_plain_http_remotes() {
+        let url = Url::parse("http://mcp.example.com/mcp").unwrap();
+        let client = Client::new();
+        let result = check_token_audience(&client, &url, "https://abc.supabase.co/auth/v1").await;
+        assert!(result.is_err());
+    }
+}
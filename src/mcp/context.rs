@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use crate::auth::AuthHandler;
+use crate::config::Config;
+use crate::error::McpResult;
+use crate::session::Session;
+use crate::supabase::{SupabaseClient, UserProfile};
+
+/// Who an MCP request runs as
+enum Caller {
+    /// The user signed in to the local profile, whose session is refreshed as needed
+    Local,
+    /// A remote client that presented a validated access token
+    Bearer {
+        token: String,
+        user: Box<UserProfile>,
+    },
+}
+
+/// The identity and Supabase project a request runs against
+pub struct RequestContext {
+    auth: Arc<AuthHandler>,
+    caller: Caller,
+}
+
+impl RequestContext {
+    /// A request made with the session stored for the local profile
+    pub fn local(auth: Arc<AuthHandler>) -> Self {
+        Self {
+            auth,
+            caller: Caller::Local,
+        }
+    }
+
+    /// A request authenticated with a bearer token that has already been validated
+    pub fn bearer(auth: Arc<AuthHandler>, token: String, user: UserProfile) -> Self {
+        Self {
+            auth,
+            caller: Caller::Bearer {
+                token,
+                user: Box::new(user),
+            },
+        }
+    }
+
+    /// The caller's access token
+    pub async fn access_token(&self) -> McpResult<String> {
+        match &self.caller {
+            Caller::Local => self.auth.get_token().await,
+            Caller::Bearer { token, .. } => Ok(token.clone()),
+        }
+    }
+
+    /// The caller's session; bearer callers have no refresh token
+    pub async fn session(&self) -> McpResult<Session> {
+        match &self.caller {
+            Caller::Local => self.auth.get_session().await,
+            Caller::Bearer { token, .. } => Ok(Session::from_access_token(token.clone())),
+        }
+    }
+
+    /// The caller's Supabase user profile
+    pub async fn user_profile(&self) -> McpResult<UserProfile> {
+        match &self.caller {
+            Caller::Local => {
+                let token = self.auth.get_token().await?;
+                self.auth.supabase().get_user_profile(&token).await
+            }
+            Caller::Bearer { user, .. } => Ok(user.as_ref().clone()),
+        }
+    }
+
+    /// ID of a bearer caller; the local user's is only known after asking Supabase
+    pub fn user_id(&self) -> Option<&str> {
+        match &self.caller {
+            Caller::Local => None,
+            Caller::Bearer { user, .. } => Some(&user.id),
+        }
+    }
+
+    /// The profile's auth handler, for work that outlives the request
+    pub fn auth(&self) -> &Arc<AuthHandler> {
+        &self.auth
+    }
+
+    /// Settings of the profile the server runs with
+    pub fn config(&self) -> &Config {
+        self.auth.config()
+    }
+
+    /// Client for the profile's Supabase project
+    pub fn supabase(&self) -> &SupabaseClient {
+        self.auth.supabase()
+    }
+}
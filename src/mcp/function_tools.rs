@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use crate::config::FunctionTool;
+use crate::error::{McpError, McpResult};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::{CallToolResult, ToolDefinition};
+use crate::mcp::tools::{Tool, ToolRegistry, parse_arguments};
+
+/// Longest tool name MCP clients are expected to accept
+const MAX_TOOL_NAME_LENGTH: usize = 128;
+
+/// Register a tool for each Edge Function declared in the profile
+///
+/// Fails on names that are not valid tool names or that are already taken, by a built-in
+/// tool or another function, since calls by name could only ever reach one of them.
+pub fn register(registry: &mut ToolRegistry, functions: &[FunctionTool]) -> McpResult<()> {
+    for function in functions {
+        check_tool_name(&function.name)?;
+        if registry.get(&function.name).is_some() {
+            return Err(McpError::ConfigError(format!(
+                "Function tool '{}' is declared twice or clashes with a built-in tool",
+                function.name
+            )));
+        }
+
+        registry.register(Box::new(EdgeFunctionTool {
+            function: function.clone(),
+        }));
+    }
+
+    Ok(())
+}
+
+/// Tool names are 1 to 128 ASCII letters, digits, `_`, `-` and `.`
+fn check_tool_name(name: &str) -> McpResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_TOOL_NAME_LENGTH
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(McpError::ConfigError(format!(
+            "Function '{}' cannot be a tool: names may only use letters, digits, '_', '-' and '.', \
+             up to {} characters",
+            name, MAX_TOOL_NAME_LENGTH
+        )))
+    }
+}
+
+/// Invokes an Edge Function with the tool arguments as its JSON body
+struct EdgeFunctionTool {
+    function: FunctionTool,
+}
+
+#[async_trait]
+impl Tool for EdgeFunctionTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.function.name.clone(),
+            description: Some(self.function.description.clone().unwrap_or_else(|| {
+                format!("Invoke the {} Supabase Edge Function", self.function.name)
+            })),
+            input_schema: self.function.input_schema.clone(),
+            output_schema: None,
+        }
+    }
+
+    async fn call(&self, ctx: &RequestContext, arguments: Value) -> McpResult<CallToolResult> {
+        let arguments: Map<String, Value> = parse_arguments(arguments)?;
+
+        let token = ctx.access_token().await?;
+        let headers = [("Content-Type".to_string(), "application/json".to_string())];
+        let response = ctx
+            .supabase()
+            .invoke_function(
+                &token,
+                &self.function.name,
+                Some(serde_json::to_vec(&arguments)?),
+                &headers,
+            )
+            .await?;
+
+        let text = response.text().await?;
+        Ok(CallToolResult::text(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::session_tools;
+    use serde_json::json;
+
+    /// Register the functions a profile declares next to the built-in tools
+    fn register_declared(functions: Value) -> McpResult<ToolRegistry> {
+        let functions: Vec<FunctionTool> = serde_json::from_value(functions).unwrap();
+        let mut registry = ToolRegistry::default();
+        session_tools::register(&mut registry);
+        register(&mut registry, &functions).map(|()| registry)
+    }
+
+    #[test]
+    fn declared_functions_become_tools() {
+        let registry = register_declared(json!([
+            { "name": "summarize" },
+            {
+                "name": "import-csv.v2",
+                "description": "Import a CSV file",
+                "input_schema": { "type": "object", "required": ["url"] },
+            },
+        ]))
+        .unwrap();
+
+        let summarize = registry.get("summarize").unwrap().definition();
+        assert_eq!(
+            summarize.description.as_deref(),
+            Some("Invoke the summarize Supabase Edge Function")
+        );
+        assert_eq!(summarize.input_schema, json!({ "type": "object" }));
+
+        let import = registry.get("import-csv.v2").unwrap().definition();
+        assert_eq!(import.description.as_deref(), Some("Import a CSV file"));
+        assert_eq!(import.input_schema["required"], json!(["url"]));
+    }
+
+    #[test]
+    fn names_already_taken_are_rejected() {
+        let builtin = register_declared(json!([{ "name": "whoami" }]));
+        assert!(matches!(builtin, Err(McpError::ConfigError(_))));
+
+        let twice = register_declared(json!([{ "name": "summarize" }, { "name": "summarize" }]));
+        assert!(matches!(twice, Err(McpError::ConfigError(_))));
+    }
+
+    #[test]
+    fn names_outside_the_tool_charset_are_rejected() {
+        let too_long = "f".repeat(MAX_TOOL_NAME_LENGTH + 1);
+        for name in ["", "hello world", "run/job", "héllo", too_long.as_str()] {
+            assert!(check_tool_name(name).is_err(), "{:?}", name);
+        }
+        assert!(check_tool_name(&"f".repeat(MAX_TOOL_NAME_LENGTH)).is_ok());
+    }
+}
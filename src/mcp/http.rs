@@ -0,0 +1,607 @@
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::{get, post};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use futures::Stream;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use url::{Host, Url};
+
+use crate::auth::AuthHandler;
+use crate::discovery::ProtectedResourceMetadata;
+use crate::error::{McpError, McpResult};
+use crate::jwt::TokenValidator;
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::JsonRpcMessage;
+use crate::mcp::server::{McpServer, McpSession, SERVER_NAME};
+use crate::supabase::UserProfile;
+
+/// Path of the single MCP endpoint
+pub const MCP_PATH: &str = "/mcp";
+
+/// Path of the protected-resource metadata named in `WWW-Authenticate`
+pub const RESOURCE_METADATA_PATH: &str = "/.well-known/oauth-protected-resource";
+
+/// Header carrying the ID of the MCP session a request belongs to
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// Number of random bytes in a session ID
+const SESSION_ID_BYTES: usize = 32;
+
+/// Sessions without requests for this long are dropped
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// How long a resolved user profile is reused for the same token
+const PROFILE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// An MCP session opened over HTTP
+struct HttpSession {
+    /// ID of the Supabase user that initialized the session
+    owner: String,
+    state: Arc<McpSession>,
+    last_seen: Instant,
+}
+
+/// Why a request was turned away before reaching the MCP server
+enum Rejection {
+    /// Missing or invalid credentials, with the `WWW-Authenticate` challenge to send
+    Unauthorized(String),
+    Status(StatusCode, String),
+}
+
+impl Rejection {
+    fn status(status: StatusCode, message: impl Into<String>) -> Self {
+        Rejection::Status(status, message.into())
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection::Unauthorized(challenge) => {
+                let mut response =
+                    (StatusCode::UNAUTHORIZED, "Authentication required").into_response();
+                if let Ok(value) = HeaderValue::from_str(&challenge) {
+                    response
+                        .headers_mut()
+                        .insert(header::WWW_AUTHENTICATE, value);
+                }
+                response
+            }
+            Rejection::Status(status, message) => (status, message).into_response(),
+        }
+    }
+}
+
+struct CachedProfile {
+    user: UserProfile,
+    fetched_at: Instant,
+}
+
+struct HttpState {
+    server: Arc<McpServer>,
+    auth: Arc<AuthHandler>,
+    validator: TokenValidator,
+    /// Address the server listens on
+    addr: SocketAddr,
+    /// Externally visible base URL, when the server sits behind a proxy
+    public_url: Option<Url>,
+    sessions: Mutex<HashMap<String, HttpSession>>,
+    profiles: Mutex<HashMap<String, CachedProfile>>,
+}
+
+/// Serve MCP over streamable HTTP on `addr`
+///
+/// Every request must carry a Supabase access token for the configured project as a bearer
+/// token; tools run as the user it belongs to.
+pub async fn serve_http(
+    server: Arc<McpServer>,
+    auth: Arc<AuthHandler>,
+    addr: SocketAddr,
+    public_url: Option<Url>,
+) -> McpResult<()> {
+    let state = Arc::new(HttpState {
+        server,
+        validator: TokenValidator::new(auth.config().clone()),
+        auth,
+        addr,
+        public_url,
+        sessions: Mutex::new(HashMap::new()),
+        profiles: Mutex::new(HashMap::new()),
+    });
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    eprintln!(
+        "MCP server listening on http://{}{}",
+        listener.local_addr()?,
+        MCP_PATH
+    );
+
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+fn router(state: Arc<HttpState>) -> Router {
+    // Clients may look for the metadata with or without the resource path appended
+    Router::new()
+        .route(
+            MCP_PATH,
+            post(handle_post).get(handle_get).delete(handle_delete),
+        )
+        .route(RESOURCE_METADATA_PATH, get(handle_resource_metadata))
+        .route(
+            &format!("{}{}", RESOURCE_METADATA_PATH, MCP_PATH),
+            get(handle_resource_metadata),
+        )
+        .with_state(state)
+}
+
+/// POST: one JSON-RPC message from the client
+async fn handle_post(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, Rejection> {
+    state.check_origin(&headers)?;
+    let (token, user) = state.authenticate(&headers).await?;
+
+    let message = match JsonRpcMessage::parse(&body) {
+        Ok(message) => message,
+        Err(error) => return Ok((StatusCode::BAD_REQUEST, Json(error)).into_response()),
+    };
+
+    let is_initialize =
+        matches!(&message, JsonRpcMessage::Request(request) if request.method == "initialize");
+
+    // `initialize` starts a new session; everything else must name one
+    let (session_id, session) = if is_initialize {
+        (None, Arc::new(McpSession::default()))
+    } else {
+        let (id, session) = state.find_session(&headers, &user.id)?;
+        (Some(id), session)
+    };
+
+    let owner = user.id.clone();
+    let ctx = RequestContext::bearer(state.auth.clone(), token, user);
+    let reply = state.server.handle_message(&session, &ctx, message).await;
+
+    let mut response = match &reply {
+        Some(reply) => Json(reply).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+
+    match session_id {
+        Some(id) if session.is_shut_down() => state.remove_session(&id),
+        Some(_) => {}
+        // Only a successful handshake gets a session
+        None => {
+            if let Some(JsonRpcMessage::Response(_)) = reply {
+                let id = state.create_session(owner, session);
+                if let Ok(value) = HeaderValue::from_str(&id) {
+                    response.headers_mut().insert(SESSION_ID_HEADER, value);
+                }
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+/// GET: an SSE stream of server-initiated messages for the session
+async fn handle_get(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+) -> Result<Response, Rejection> {
+    state.check_origin(&headers)?;
+    let (_, user) = state.authenticate(&headers).await?;
+    let outbound = state.subscribe(&headers, &user.id)?;
+
+    Ok(Sse::new(event_stream(outbound))
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// DELETE: the client is done with the session
+async fn handle_delete(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Rejection> {
+    state.check_origin(&headers)?;
+    let (_, user) = state.authenticate(&headers).await?;
+    let (id, _) = state.find_session(&headers, &user.id)?;
+
+    state.remove_session(&id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The protected-resource metadata naming the Supabase project as authorization server
+async fn handle_resource_metadata(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = state.check_host(&headers) {
+        return rejection.into_response();
+    }
+
+    let metadata = ProtectedResourceMetadata {
+        resource: format!("{}{}", state.base_url(&headers), MCP_PATH),
+        authorization_servers: vec![state.auth.config().auth_issuer()],
+        bearer_methods_supported: vec!["header".to_string()],
+        resource_name: Some(SERVER_NAME.to_string()),
+    };
+
+    // Browser-based clients fetch this before they have a token
+    ([(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")], Json(metadata)).into_response()
+}
+
+fn event_stream(
+    outbound: broadcast::Receiver<JsonRpcMessage>,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    futures::stream::unfold(outbound, |mut outbound| async move {
+        loop {
+            match outbound.recv().await {
+                Ok(message) => return Some((Event::default().json_data(&message), outbound)),
+                // A slow client misses messages rather than holding up the session
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+impl HttpState {
+    /// Resolve the bearer token of a request to the Supabase user it belongs to
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<(String, UserProfile), Rejection> {
+        let token = bearer_token(headers)
+            .ok_or_else(|| self.unauthorized(headers, None))?
+            .to_string();
+
+        // Without the secret of an HS256 project only Supabase can check the signature, which
+        // the profile lookup below does
+        let checked = if self.validator.can_verify(&token) {
+            self.validator.verify(&token).await.map(|_| ())
+        } else {
+            self.validator.decode(&token).map(|_| ())
+        };
+        checked.map_err(|err| self.unauthorized(headers, Some(&err)))?;
+
+        if let Some(user) = self.cached_profile(&token) {
+            return Ok((token, user));
+        }
+
+        // Asking Supabase also catches sessions that were revoked before the token expired
+        let user = match self.auth.supabase().get_user_profile(&token).await {
+            Ok(user) => user,
+            Err(err @ McpError::AuthError(_)) => return Err(self.unauthorized(headers, Some(&err))),
+            Err(err) => return Err(Rejection::status(StatusCode::BAD_GATEWAY, err.to_string())),
+        };
+
+        let mut profiles = self.profiles.lock().unwrap();
+        profiles.retain(|_, cached| cached.fetched_at.elapsed() < PROFILE_CACHE_TTL);
+        profiles.insert(
+            token.clone(),
+            CachedProfile {
+                user: user.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+
+        Ok((token, user))
+    }
+
+    fn cached_profile(&self, token: &str) -> Option<UserProfile> {
+        let profiles = self.profiles.lock().unwrap();
+        profiles
+            .get(token)
+            .filter(|cached| cached.fetched_at.elapsed() < PROFILE_CACHE_TTL)
+            .map(|cached| cached.user.clone())
+    }
+
+    /// A 401 pointing the client at the protected-resource metadata
+    fn unauthorized(&self, headers: &HeaderMap, error: Option<&McpError>) -> Rejection {
+        let metadata_url = format!("{}{}", self.base_url(headers), RESOURCE_METADATA_PATH);
+
+        let mut challenge = format!("Bearer resource_metadata=\"{}\"", metadata_url);
+        if let Some(error) = error {
+            let description = error.to_string().replace(['"', '\\'], "'");
+            challenge.push_str(&format!(
+                ", error=\"invalid_token\", error_description=\"{}\"",
+                description
+            ));
+        }
+
+        Rejection::Unauthorized(challenge)
+    }
+
+    /// Base URL clients reach this server at
+    fn base_url(&self, headers: &HeaderMap) -> String {
+        if let Some(url) = &self.public_url {
+            return url.as_str().trim_end_matches('/').to_string();
+        }
+
+        let host = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or("localhost");
+        format!("http://{}", host)
+    }
+
+    /// Reject browser requests from other origins, which could come from a DNS rebinding attack
+    fn check_origin(&self, headers: &HeaderMap) -> Result<(), Rejection> {
+        self.check_host(headers)?;
+
+        let Some(origin) = headers.get(header::ORIGIN) else {
+            return Ok(());
+        };
+
+        let allowed = origin
+            .to_str()
+            .ok()
+            .and_then(|origin| Url::parse(origin).ok())
+            .is_some_and(|origin| {
+                let public = self
+                    .public_url
+                    .as_ref()
+                    .is_some_and(|url| url.origin() == origin.origin());
+                public || origin.host().is_some_and(|host| self.is_local_host(&host))
+            });
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Rejection::status(
+                StatusCode::FORBIDDEN,
+                "Origin not allowed",
+            ))
+        }
+    }
+
+    /// Reject requests for host names other than this server's
+    ///
+    /// A rebound DNS name sends the attacker's host name, which the browser considers the
+    /// origin too, so the `Origin` check alone cannot catch it.
+    fn check_host(&self, headers: &HeaderMap) -> Result<(), Rejection> {
+        let Some(host) = headers.get(header::HOST) else {
+            return Ok(());
+        };
+
+        let allowed = host
+            .to_str()
+            .ok()
+            .and_then(|host| Url::parse(&format!("http://{}", host)).ok())
+            .is_some_and(|url| {
+                url.host().is_some_and(|host| {
+                    self.is_local_host(&host)
+                        || self.public_url.as_ref().and_then(Url::host) == Some(host)
+                })
+            });
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Rejection::status(StatusCode::FORBIDDEN, "Host not allowed"))
+        }
+    }
+
+    /// Whether `host` is a loopback name or the address the server listens on
+    fn is_local_host(&self, host: &Host<&str>) -> bool {
+        let ip = match *host {
+            Host::Domain(domain) => return domain.eq_ignore_ascii_case("localhost"),
+            Host::Ipv4(ip) => IpAddr::V4(ip),
+            Host::Ipv6(ip) => IpAddr::V6(ip),
+        };
+
+        ip.is_loopback() || (ip == self.addr.ip() && !ip.is_unspecified())
+    }
+
+    fn create_session(&self, owner: String, state: Arc<McpSession>) -> String {
+        let mut bytes = [0u8; SESSION_ID_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let id = URL_SAFE_NO_PAD.encode(bytes);
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.last_seen.elapsed() < SESSION_IDLE_TIMEOUT);
+        sessions.insert(
+            id.clone(),
+            HttpSession {
+                owner,
+                state,
+                last_seen: Instant::now(),
+            },
+        );
+
+        id
+    }
+
+    /// Look up the session a request names, which must belong to the same user
+    fn find_session(
+        &self,
+        headers: &HeaderMap,
+        user_id: &str,
+    ) -> Result<(String, Arc<McpSession>), Rejection> {
+        self.with_session(headers, user_id, |id, session| {
+            (id.to_string(), session.state.clone())
+        })
+    }
+
+    fn subscribe(
+        &self,
+        headers: &HeaderMap,
+        user_id: &str,
+    ) -> Result<broadcast::Receiver<JsonRpcMessage>, Rejection> {
+        self.with_session(headers, user_id, |_, session| session.state.notifications())
+    }
+
+    fn with_session<T>(
+        &self,
+        headers: &HeaderMap,
+        user_id: &str,
+        f: impl FnOnce(&str, &HttpSession) -> T,
+    ) -> Result<T, Rejection> {
+        let id = headers
+            .get(SESSION_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .ok_or_else(|| {
+                Rejection::status(StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header")
+            })?;
+
+        let mut sessions = self.sessions.lock().unwrap();
+
+        // Another user's session is reported as missing rather than forbidden
+        match sessions.get_mut(id) {
+            Some(session) if session.owner == user_id => {
+                session.last_seen = Instant::now();
+                Ok(f(id, session))
+            }
+            _ => Err(Rejection::status(
+                StatusCode::NOT_FOUND,
+                "Unknown MCP session",
+            )),
+        }
+    }
+
+    fn remove_session(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
+
+/// The token of an `Authorization: Bearer` header
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+
+    (scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty()).then(|| token.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::credentials::CredentialStoreKind;
+    use reqwest::Client;
+
+    /// Serve the endpoints on an ephemeral loopback port and return its address
+    async fn serve(public_url: Option<&str>) -> SocketAddr {
+        let config = Config {
+            supabase_url: "https://abc.supabase.co".to_string(),
+            credential_store: CredentialStoreKind::Env,
+            ..Config::default()
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(HttpState {
+            server: Arc::new(McpServer::new(&config).unwrap()),
+            validator: TokenValidator::new(config.clone()),
+            auth: Arc::new(AuthHandler::new(config)),
+            addr,
+            public_url: public_url.map(|url| Url::parse(url).unwrap()),
+            sessions: Mutex::new(HashMap::new()),
+            profiles: Mutex::new(HashMap::new()),
+        });
+
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+        addr
+    }
+
+    #[tokio::test]
+    async fn metadata_names_the_project_and_rejects_rebound_hosts() {
+        let addr = serve(None).await;
+        let url = format!("http://{}{}", addr, RESOURCE_METADATA_PATH);
+
+        let metadata: ProtectedResourceMetadata = Client::new()
+            .get(&url)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(metadata.resource, format!("http://{}{}", addr, MCP_PATH));
+        assert!(metadata.names_authorization_server("https://abc.supabase.co/auth/v1"));
+
+        let rebound = Client::new()
+            .get(&url)
+            .header("host", "attacker.example.com")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rebound.status().as_u16(), StatusCode::FORBIDDEN.as_u16());
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_valid_token_are_challenged() {
+        let addr = serve(Some("https://mcp.example.com")).await;
+        let url = format!("http://{}{}", addr, MCP_PATH);
+
+        let response = Client::new().post(&url).body("{}").send().await.unwrap();
+        assert_eq!(
+            response.status().as_u16(),
+            StatusCode::UNAUTHORIZED.as_u16()
+        );
+        assert_eq!(
+            response.headers()["www-authenticate"],
+            "Bearer resource_metadata=\"https://mcp.example.com/.well-known/oauth-protected-resource\""
+        );
+
+        let response = Client::new()
+            .post(&url)
+            .bearer_auth("not-a-jwt")
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        let challenge = response.headers()["www-authenticate"].to_str().unwrap();
+        assert!(
+            challenge.contains("error=\"invalid_token\""),
+            "{}",
+            challenge
+        );
+    }
+
+    #[tokio::test]
+    async fn cross_origin_requests_are_refused() {
+        let addr = serve(Some("https://mcp.example.com")).await;
+        let url = format!("http://{}{}", addr, MCP_PATH);
+
+        for (origin, status) in [
+            ("https://attacker.example.com", StatusCode::FORBIDDEN),
+            ("null", StatusCode::FORBIDDEN),
+            ("https://mcp.example.com", StatusCode::UNAUTHORIZED),
+            ("http://localhost:3000", StatusCode::UNAUTHORIZED),
+        ] {
+            let response = Client::new()
+                .post(&url)
+                .header("origin", origin)
+                .body("{}")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), status.as_u16(), "{}", origin);
+        }
+    }
+
+    #[test]
+    fn bearer_token_requires_the_bearer_scheme() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+
+        for (value, token) in [
+            ("bearer abc", Some("abc")),
+            ("Basic abc", None),
+            ("Bearer  ", None),
+        ] {
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_static(value));
+            assert_eq!(bearer_token(&headers), token, "{}", value);
+        }
+    }
+}
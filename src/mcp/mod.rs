@@ -0,0 +1,13 @@
+pub mod client;
+pub mod context;
+pub mod function_tools;
+pub mod http;
+pub mod protocol;
+pub mod proxy;
+pub mod resources;
+pub mod server;
+pub mod session_tools;
+pub mod stdio;
+pub mod subscriptions;
+pub mod table_resources;
+pub mod tools;
@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::McpError;
+
+/// JSON-RPC version string carried by every message
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// MCP protocol revisions this crate speaks, newest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+// Standard JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// MCP error code for a `resources/read` of an unknown URI
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+/// Key under which tool results carry the kind of error that failed them
+const ERROR_META_KEY: &str = "rust-mcp/error";
+
+/// A JSON-RPC request ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+/// A request that expects a response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+/// A one-way message without an ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+/// A successful response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    pub result: Value,
+}
+
+/// An error response; the ID is null when the request could not be parsed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcErrorResponse {
+    pub jsonrpc: String,
+    pub id: Option<RequestId>,
+    pub error: JsonRpcError,
+}
+
+/// The error object of an error response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// Any JSON-RPC message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcMessage {
+    Request(JsonRpcRequest),
+    Notification(JsonRpcNotification),
+    Response(JsonRpcResponse),
+    Error(JsonRpcErrorResponse),
+}
+
+impl JsonRpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<McpError> for JsonRpcError {
+    fn from(err: McpError) -> Self {
+        let code = match err {
+            McpError::InvalidArguments(_) => INVALID_PARAMS,
+            McpError::ResourceNotFound(_) => RESOURCE_NOT_FOUND,
+            _ => INTERNAL_ERROR,
+        };
+
+        Self {
+            code,
+            message: err.to_string(),
+            data: Some(json!({ "kind": err.kind() })),
+        }
+    }
+}
+
+impl JsonRpcMessage {
+    /// Build a successful response
+    pub fn response(id: RequestId, result: Value) -> Self {
+        JsonRpcMessage::Response(JsonRpcResponse {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result,
+        })
+    }
+
+    /// Build a notification
+    pub fn notification(method: impl Into<String>, params: Value) -> Self {
+        JsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.into(),
+            params: Some(params),
+        })
+    }
+
+    /// Build an error response
+    pub fn error(id: Option<RequestId>, error: JsonRpcError) -> Self {
+        JsonRpcMessage::Error(JsonRpcErrorResponse {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            error,
+        })
+    }
+
+    /// Parse a single message, producing the error response to send back on failure
+    #[allow(clippy::result_large_err)] // The error is a whole message, sent back as it is
+    pub fn parse(text: &str) -> Result<Self, JsonRpcMessage> {
+        let value: Value = serde_json::from_str(text)
+            .map_err(|e| Self::error(None, JsonRpcError::new(PARSE_ERROR, e.to_string())))?;
+
+        if value.get("jsonrpc").and_then(Value::as_str) != Some(JSONRPC_VERSION) {
+            return Err(Self::error(
+                None,
+                JsonRpcError::new(INVALID_REQUEST, "Expected a JSON-RPC 2.0 message"),
+            ));
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| Self::error(None, JsonRpcError::new(INVALID_REQUEST, e.to_string())))
+    }
+}
+
+/// A tool as advertised by `tools/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub input_schema: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>,
+}
+
+/// A content block in a tool result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Content {
+    Text { text: String },
+}
+
+/// The result of `tools/call`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    pub content: Vec<Content>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+    #[serde(default)]
+    pub is_error: bool,
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+impl CallToolResult {
+    /// A successful result with a text block
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            content: vec![Content::Text { text: text.into() }],
+            ..Default::default()
+        }
+    }
+
+    /// A successful result with structured content, mirrored as JSON text for older clients
+    pub fn structured(value: Value) -> Self {
+        Self {
+            content: vec![Content::Text {
+                text: value.to_string(),
+            }],
+            structured_content: Some(value),
+            ..Default::default()
+        }
+    }
+
+    /// A failed tool execution, reported to the model rather than as a protocol error
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            ..Self::text(message)
+        }
+    }
+}
+
+impl From<McpError> for CallToolResult {
+    fn from(err: McpError) -> Self {
+        // Structured content is left out: it would have to match the tool's output schema
+        Self {
+            meta: Some(json!({ ERROR_META_KEY: { "kind": err.kind() } })),
+            ..Self::error(err.to_string())
+        }
+    }
+}
+
+/// A resource as advertised by `resources/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A family of resources as advertised by `resources/templates/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    /// RFC 6570 URI template
+    pub uri_template: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Text contents of a resource, as returned by `resources/read`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(message: JsonRpcMessage) -> i64 {
+        match message {
+            JsonRpcMessage::Error(response) => response.error.code,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_each_kind_of_message() {
+        let request = JsonRpcMessage::parse(r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#).unwrap();
+        assert!(
+            matches!(request, JsonRpcMessage::Request(request) if request.id == RequestId::Number(1))
+        );
+
+        let notification =
+            JsonRpcMessage::parse(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#)
+                .unwrap();
+        assert!(matches!(notification, JsonRpcMessage::Notification(_)));
+
+        let response = JsonRpcMessage::parse(r#"{"jsonrpc":"2.0","id":"a","result":{}}"#).unwrap();
+        assert!(
+            matches!(response, JsonRpcMessage::Response(response) if response.id == RequestId::String("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_failures_become_error_responses() {
+        assert_eq!(
+            error_code(JsonRpcMessage::parse("{").unwrap_err()),
+            PARSE_ERROR
+        );
+        assert_eq!(
+            error_code(JsonRpcMessage::parse(r#"{"id":1,"method":"ping"}"#).unwrap_err()),
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            error_code(JsonRpcMessage::parse(r#"{"jsonrpc":"2.0","id":1}"#).unwrap_err()),
+            INVALID_REQUEST
+        );
+    }
+
+    #[test]
+    fn errors_map_to_json_rpc_codes() {
+        let invalid = JsonRpcError::from(McpError::InvalidArguments("bad".to_string()));
+        assert_eq!(invalid.code, INVALID_PARAMS);
+
+        let missing = JsonRpcError::from(McpError::ResourceNotFound("uri".to_string()));
+        assert_eq!(missing.code, RESOURCE_NOT_FOUND);
+
+        let other = JsonRpcError::from(McpError::AuthError("nope".to_string()));
+        assert_eq!(other.code, INTERNAL_ERROR);
+    }
+}
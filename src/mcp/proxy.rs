@@ -0,0 +1,152 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinSet;
+use url::Url;
+
+use crate::auth::AuthHandler;
+use crate::error::{McpError, McpResult, to_other_error};
+use crate::mcp::client::{HttpTransport, response_id};
+use crate::mcp::protocol::JsonRpcMessage;
+use crate::mcp::stdio::spawn_stdout_writer;
+
+/// First wait before reopening a dropped notification stream
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+
+/// Longest wait before reopening a dropped notification stream
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
+/// Relay MCP between stdin/stdout and the streamable HTTP server at `url`
+///
+/// Every message is forwarded with the profile's access token, which is refreshed when the
+/// server rejects it. Server-initiated messages are written to stdout as they arrive.
+pub async fn run_proxy(url: Url, auth: Arc<AuthHandler>) -> McpResult<()> {
+    let transport = Arc::new(HttpTransport::connect(url, auth).await?);
+    let (tx, writer) = spawn_stdout_writer();
+
+    let mut in_flight = JoinSet::new();
+    let mut listener = None;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message = match JsonRpcMessage::parse(&line) {
+            Ok(message) => message,
+            Err(error) => {
+                let _ = tx.send(error);
+                continue;
+            }
+        };
+
+        // Requests can take a while, so they run concurrently; the handshake and everything
+        // that is not a request goes out in order, so later requests carry the session ID
+        let runs_concurrently = matches!(
+            &message,
+            JsonRpcMessage::Request(request) if request.method != "initialize"
+        );
+        let starts_session = matches!(
+            &message,
+            JsonRpcMessage::Notification(notification)
+                if notification.method == "notifications/initialized"
+        );
+
+        if runs_concurrently {
+            let transport = transport.clone();
+            let tx = tx.clone();
+            in_flight.spawn(async move { forward(&transport, message, &tx).await });
+        } else {
+            forward(&transport, message, &tx).await;
+        }
+
+        if starts_session && listener.is_none() {
+            listener = Some(tokio::spawn(relay_server_messages(
+                transport.clone(),
+                tx.clone(),
+            )));
+        }
+    }
+
+    // Let running requests answer before the session is closed
+    while in_flight.join_next().await.is_some() {}
+    if let Some(listener) = listener {
+        listener.abort();
+    }
+    transport.close().await?;
+    drop(tx);
+
+    writer.await.map_err(to_other_error)?
+}
+
+/// Send one message to the server and relay whatever comes back
+async fn forward(
+    transport: &HttpTransport,
+    message: JsonRpcMessage,
+    tx: &UnboundedSender<JsonRpcMessage>,
+) {
+    let request_id = match &message {
+        JsonRpcMessage::Request(request) => Some(request.id.clone()),
+        _ => None,
+    };
+
+    let mut answered = false;
+    let result = match transport.post(&message).await {
+        Ok(response) => {
+            transport
+                .read_messages(response, |incoming| {
+                    answered |=
+                        request_id.is_some() && response_id(&incoming) == request_id.as_ref();
+                    let _ = tx.send(incoming);
+                    answered
+                })
+                .await
+        }
+        Err(err) => Err(err),
+    };
+
+    let Some(id) = request_id else {
+        if let Err(err) = result {
+            eprintln!("Failed to forward message: {}", err);
+        }
+        return;
+    };
+
+    // The local client is waiting for an answer, so failures become error responses
+    let error = match result {
+        Ok(()) if answered => return,
+        Ok(()) => McpError::RemoteError("The MCP server sent no response".to_string()),
+        Err(err) => err,
+    };
+    let _ = tx.send(JsonRpcMessage::error(Some(id), error.into()));
+}
+
+/// Relay messages from the server's notification stream, reopening it when it drops
+async fn relay_server_messages(transport: Arc<HttpTransport>, tx: UnboundedSender<JsonRpcMessage>) {
+    let mut delay = RECONNECT_DELAY_MIN;
+
+    loop {
+        match transport.listen().await {
+            // The server has no stream to offer
+            Ok(None) => return,
+            Ok(Some(response)) => {
+                delay = RECONNECT_DELAY_MIN;
+                let relayed = transport
+                    .read_messages(response, |message| {
+                        let _ = tx.send(message);
+                        false
+                    })
+                    .await;
+                if let Err(err) = relayed {
+                    eprintln!("Notification stream dropped: {}", err);
+                }
+            }
+            Err(err) => eprintln!("Failed to open notification stream: {}", err),
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+    }
+}
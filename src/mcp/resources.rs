@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use url::Url;
+
+use crate::error::{McpError, McpResult};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::{Resource, ResourceContents, ResourceTemplate};
+
+/// A source of resources the MCP server exposes through `resources/*`
+#[async_trait]
+pub trait ResourceProvider: Send + Sync {
+    /// Templates for resources that are not listed one by one
+    fn templates(&self) -> Vec<ResourceTemplate>;
+
+    /// Resources available to the caller
+    async fn list(&self, ctx: &RequestContext) -> McpResult<Vec<Resource>>;
+
+    /// Read `uri` as the caller, or return `None` if this provider does not serve it
+    async fn read(&self, ctx: &RequestContext, uri: &Url) -> McpResult<Option<ResourceContents>>;
+
+    /// The database table whose changes update `uri`, or `None` if this provider does not
+    /// serve it or it cannot be watched
+    fn watched_table(&self, uri: &Url) -> McpResult<Option<String>> {
+        let _ = uri;
+        Ok(None)
+    }
+}
+
+/// The set of resource providers a server exposes
+#[derive(Default)]
+pub struct ResourceRegistry {
+    providers: Vec<Box<dyn ResourceProvider>>,
+}
+
+impl ResourceRegistry {
+    /// Add a provider to the registry
+    pub fn register(&mut self, provider: Box<dyn ResourceProvider>) {
+        self.providers.push(provider);
+    }
+
+    /// Templates of all providers
+    pub fn templates(&self) -> Vec<ResourceTemplate> {
+        self.providers
+            .iter()
+            .flat_map(|provider| provider.templates())
+            .collect()
+    }
+
+    /// Resources of all providers
+    pub async fn list(&self, ctx: &RequestContext) -> McpResult<Vec<Resource>> {
+        let mut resources = Vec::new();
+        for provider in &self.providers {
+            resources.extend(provider.list(ctx).await?);
+        }
+        Ok(resources)
+    }
+
+    /// Read a resource from whichever provider serves it
+    pub async fn read(&self, ctx: &RequestContext, uri: &str) -> McpResult<ResourceContents> {
+        let parsed = Url::parse(uri).map_err(|_| McpError::ResourceNotFound(uri.to_string()))?;
+
+        for provider in &self.providers {
+            if let Some(contents) = provider.read(ctx, &parsed).await? {
+                return Ok(contents);
+            }
+        }
+
+        Err(McpError::ResourceNotFound(uri.to_string()))
+    }
+
+    /// The table to watch for changes to a resource, for `resources/subscribe`
+    pub fn watched_table(&self, uri: &str) -> McpResult<String> {
+        let parsed = Url::parse(uri).map_err(|_| McpError::ResourceNotFound(uri.to_string()))?;
+
+        for provider in &self.providers {
+            if let Some(table) = provider.watched_table(&parsed)? {
+                return Ok(table);
+            }
+        }
+
+        Err(McpError::ResourceNotFound(uri.to_string()))
+    }
+}
@@ -0,0 +1,395 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::config::Config;
+use crate::error::{McpError, McpResult};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::{
+    CallToolResult, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST, JsonRpcError, JsonRpcMessage,
+    JsonRpcNotification, JsonRpcRequest, METHOD_NOT_FOUND, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use crate::mcp::resources::ResourceRegistry;
+use crate::mcp::subscriptions::Subscriptions;
+use crate::mcp::tools::ToolRegistry;
+use crate::mcp::{function_tools, session_tools, table_resources};
+
+/// Name the server reports in `initialize` and its resource metadata
+pub const SERVER_NAME: &str = "rust-mcp";
+
+/// Capacity of each session's queue of server-initiated messages
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// Where a client connection is in the MCP lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Phase {
+    /// Waiting for `initialize`
+    #[default]
+    Uninitialized,
+    /// `initialize` answered, waiting for `notifications/initialized`
+    Initializing,
+    /// Normal operation
+    Ready,
+    /// `shutdown` received; the transport should close
+    ShutDown,
+}
+
+/// Per-connection state
+pub struct McpSession {
+    phase: Mutex<Phase>,
+    /// Server-initiated messages, for the transport to relay to the client
+    notifications: broadcast::Sender<JsonRpcMessage>,
+}
+
+impl Default for McpSession {
+    fn default() -> Self {
+        Self {
+            phase: Mutex::default(),
+            notifications: broadcast::channel(NOTIFICATION_CAPACITY).0,
+        }
+    }
+}
+
+impl McpSession {
+    fn phase(&self) -> Phase {
+        *self.phase.lock().unwrap()
+    }
+
+    fn set_phase(&self, phase: Phase) {
+        *self.phase.lock().unwrap() = phase;
+    }
+
+    /// Whether the client asked the server to shut down
+    pub fn is_shut_down(&self) -> bool {
+        self.phase() == Phase::ShutDown
+    }
+
+    /// Receive the messages the server sends this session on its own
+    pub fn notifications(&self) -> broadcast::Receiver<JsonRpcMessage> {
+        self.notifications.subscribe()
+    }
+
+    /// Queue a message for the client; it is dropped if the transport is not listening
+    pub fn notify(&self, message: JsonRpcMessage) {
+        let _ = self.notifications.send(message);
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    protocol_version: String,
+}
+
+#[derive(Deserialize)]
+struct ResourceParams {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+struct CallToolParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+/// An MCP server exposing tools and resources that run with the caller's Supabase session
+pub struct McpServer {
+    tools: ToolRegistry,
+    resources: ResourceRegistry,
+    subscriptions: Subscriptions,
+}
+
+impl McpServer {
+    /// Create a server with the built-in tools and resources, plus the profile's function tools
+    pub fn new(config: &Config) -> McpResult<Self> {
+        let mut tools = ToolRegistry::default();
+        session_tools::register(&mut tools);
+        function_tools::register(&mut tools, &config.functions)?;
+
+        let mut resources = ResourceRegistry::default();
+        table_resources::register(&mut resources);
+
+        Ok(Self {
+            tools,
+            resources,
+            subscriptions: Subscriptions::default(),
+        })
+    }
+
+    /// Handle one incoming message and return the response to send, if any
+    pub async fn handle_message(
+        &self,
+        session: &Arc<McpSession>,
+        ctx: &RequestContext,
+        message: JsonRpcMessage,
+    ) -> Option<JsonRpcMessage> {
+        match message {
+            JsonRpcMessage::Request(request) => {
+                Some(self.handle_request(session, ctx, request).await)
+            }
+            JsonRpcMessage::Notification(notification) => {
+                self.handle_notification(session, notification);
+                None
+            }
+            // The server sends no requests of its own, so there is nothing to match these to
+            JsonRpcMessage::Response(_) | JsonRpcMessage::Error(_) => None,
+        }
+    }
+
+    async fn handle_request(
+        &self,
+        session: &Arc<McpSession>,
+        ctx: &RequestContext,
+        request: JsonRpcRequest,
+    ) -> JsonRpcMessage {
+        let id = request.id.clone();
+        self.subscriptions.update_token(ctx).await;
+
+        match self.dispatch(session, ctx, request).await {
+            Ok(result) => JsonRpcMessage::response(id, result),
+            Err(error) => JsonRpcMessage::error(Some(id), error),
+        }
+    }
+
+    async fn dispatch(
+        &self,
+        session: &Arc<McpSession>,
+        ctx: &RequestContext,
+        request: JsonRpcRequest,
+    ) -> Result<Value, JsonRpcError> {
+        match request.method.as_str() {
+            "initialize" => self.initialize(session, request.params),
+            "ping" => Ok(json!({})),
+            _ if session.phase() == Phase::Uninitialized => {
+                Err(JsonRpcError::new(INVALID_REQUEST, "Server not initialized"))
+            }
+            "tools/list" => Ok(json!({ "tools": self.tools.definitions() })),
+            "tools/call" => self.call_tool(ctx, request.params).await,
+            "resources/list" => {
+                let resources = self.resources.list(ctx).await?;
+                Ok(json!({ "resources": resources }))
+            }
+            "resources/templates/list" => {
+                Ok(json!({ "resourceTemplates": self.resources.templates() }))
+            }
+            "resources/read" => {
+                let params: ResourceParams = parse_params(request.params)?;
+                let contents = self.resources.read(ctx, &params.uri).await?;
+                Ok(json!({ "contents": [contents] }))
+            }
+            "resources/subscribe" => {
+                let params: ResourceParams = parse_params(request.params)?;
+                let table = self.resources.watched_table(&params.uri)?;
+                self.subscriptions
+                    .subscribe(ctx, session, &params.uri, &table)
+                    .await?;
+                Ok(json!({}))
+            }
+            "resources/unsubscribe" => {
+                let params: ResourceParams = parse_params(request.params)?;
+                self.subscriptions
+                    .unsubscribe(ctx, session, &params.uri)
+                    .await;
+                Ok(json!({}))
+            }
+            "shutdown" => {
+                session.set_phase(Phase::ShutDown);
+                Ok(Value::Null)
+            }
+            method => Err(JsonRpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            )),
+        }
+    }
+
+    fn handle_notification(&self, session: &McpSession, notification: JsonRpcNotification) {
+        if notification.method == "notifications/initialized"
+            && session.phase() == Phase::Initializing
+        {
+            session.set_phase(Phase::Ready);
+        }
+    }
+
+    fn initialize(
+        &self,
+        session: &McpSession,
+        params: Option<Value>,
+    ) -> Result<Value, JsonRpcError> {
+        if session.phase() != Phase::Uninitialized {
+            return Err(JsonRpcError::new(INVALID_REQUEST, "Already initialized"));
+        }
+
+        let params: InitializeParams = parse_params(params)?;
+
+        // Answer with the requested revision if we speak it, otherwise with our latest
+        let protocol_version = SUPPORTED_PROTOCOL_VERSIONS
+            .iter()
+            .find(|version| **version == params.protocol_version)
+            .unwrap_or(&SUPPORTED_PROTOCOL_VERSIONS[0]);
+
+        session.set_phase(Phase::Initializing);
+
+        Ok(json!({
+            "protocolVersion": protocol_version,
+            "capabilities": {
+                "tools": { "listChanged": false },
+                "resources": { "subscribe": true, "listChanged": false },
+            },
+            "serverInfo": {
+                "name": SERVER_NAME,
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        }))
+    }
+
+    async fn call_tool(
+        &self,
+        ctx: &RequestContext,
+        params: Option<Value>,
+    ) -> Result<Value, JsonRpcError> {
+        let params: CallToolParams = parse_params(params)?;
+
+        let tool = self.tools.get(&params.name).ok_or_else(|| {
+            JsonRpcError::new(INVALID_PARAMS, format!("Unknown tool: {}", params.name))
+        })?;
+
+        // Failures inside the tool are reported in the result so the model can see them;
+        // only malformed arguments are the caller's protocol error
+        let result = match tool.call(ctx, params.arguments).await {
+            Ok(result) => result,
+            Err(err @ McpError::InvalidArguments(_)) => return Err(err.into()),
+            Err(err) => CallToolResult::from(err),
+        };
+
+        serde_json::to_value(result).map_err(|e| JsonRpcError::new(INTERNAL_ERROR, e.to_string()))
+    }
+}
+
+/// Deserialize request parameters, mapping failures to an invalid-params error
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Option<Value>) -> Result<T, JsonRpcError> {
+    serde_json::from_value(params.unwrap_or(Value::Null))
+        .map_err(|e| JsonRpcError::new(INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthHandler;
+    use crate::credentials::CredentialStoreKind;
+
+    /// Feed a client's messages to one session, expecting each answer in turn
+    ///
+    /// An expected `Ok` is the result of a response, `Err` the code of an error and `None`
+    /// means no reply.
+    async fn exchange(transcript: Vec<(Value, Option<Result<Value, i64>>)>) -> Arc<McpSession> {
+        let config = Config {
+            supabase_url: "https://abc.supabase.co".to_string(),
+            credential_store: CredentialStoreKind::Env,
+            ..Config::default()
+        };
+        let server = McpServer::new(&config).unwrap();
+        let ctx = RequestContext::local(Arc::new(AuthHandler::new(config)));
+        let session = Arc::new(McpSession::default());
+
+        for (message, expected) in transcript {
+            let reply = server
+                .handle_message(
+                    &session,
+                    &ctx,
+                    serde_json::from_value(message.clone()).unwrap(),
+                )
+                .await;
+            let reply = reply.map(|reply| match reply {
+                JsonRpcMessage::Response(response) => Ok(response.result),
+                JsonRpcMessage::Error(response) => Err(response.error.code),
+                other => panic!("unexpected reply {:?}", other),
+            });
+            assert_eq!(reply, expected, "reply to {}", message);
+        }
+
+        session
+    }
+
+    #[tokio::test]
+    async fn lifecycle_gates_requests_until_initialized() {
+        let initialize = json!({
+            "jsonrpc": "2.0", "id": 3, "method": "initialize",
+            "params": { "protocolVersion": "2025-03-26" },
+        });
+        let initialized = json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": {
+                "tools": { "listChanged": false },
+                "resources": { "subscribe": true, "listChanged": false },
+            },
+            "serverInfo": { "name": SERVER_NAME, "version": env!("CARGO_PKG_VERSION") },
+        });
+
+        let session = exchange(vec![
+            (
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "resources/templates/list" }),
+                Some(Err(INVALID_REQUEST)),
+            ),
+            (
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" }),
+                Some(Ok(json!({}))),
+            ),
+            (initialize.clone(), Some(Ok(initialized))),
+            (
+                json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+                None,
+            ),
+            (initialize, Some(Err(INVALID_REQUEST))),
+            (
+                json!({ "jsonrpc": "2.0", "id": 4, "method": "shutdown" }),
+                Some(Ok(Value::Null)),
+            ),
+        ])
+        .await;
+
+        assert!(session.is_shut_down());
+    }
+
+    #[tokio::test]
+    async fn unknown_versions_methods_and_tools() {
+        let initialize = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "protocolVersion": "1999-01-01" },
+        });
+
+        let session = exchange(vec![
+            (
+                initialize,
+                Some(Ok(json!({
+                    "protocolVersion": SUPPORTED_PROTOCOL_VERSIONS[0],
+                    "capabilities": {
+                        "tools": { "listChanged": false },
+                        "resources": { "subscribe": true, "listChanged": false },
+                    },
+                    "serverInfo": { "name": SERVER_NAME, "version": env!("CARGO_PKG_VERSION") },
+                }))),
+            ),
+            (
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "prompts/list" }),
+                Some(Err(METHOD_NOT_FOUND)),
+            ),
+            (
+                json!({
+                    "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                    "params": { "name": "no_such_tool" },
+                }),
+                Some(Err(INVALID_PARAMS)),
+            ),
+            (
+                json!({ "jsonrpc": "2.0", "id": 4, "method": "resources/read", "params": {} }),
+                Some(Err(INVALID_PARAMS)),
+            ),
+        ])
+        .await;
+
+        assert!(!session.is_shut_down());
+    }
+}
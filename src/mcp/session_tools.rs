@@ -0,0 +1,341 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::McpResult;
+use crate::jwt::TokenValidator;
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::{CallToolResult, ToolDefinition};
+use crate::mcp::tools::{Tool, ToolRegistry, parse_arguments};
+use crate::session::now_secs;
+
+/// Register the tools that report on the signed-in user and their session
+pub fn register(registry: &mut ToolRegistry) {
+    registry.register(Box::new(WhoamiTool));
+    registry.register(Box::new(SessionStatusTool));
+    registry.register(Box::new(ListIdentitiesTool));
+}
+
+/// Arguments of tools that take none
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoArguments {}
+
+fn no_arguments_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {},
+        "additionalProperties": false,
+    })
+}
+
+fn structured<T: Serialize>(output: &T) -> McpResult<CallToolResult> {
+    Ok(CallToolResult::structured(serde_json::to_value(output)?))
+}
+
+/// Returns the Supabase user profile of the signed-in user
+struct WhoamiTool;
+
+#[async_trait]
+impl Tool for WhoamiTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "whoami".to_string(),
+            description: Some("Get the profile of the signed-in Supabase user".to_string()),
+            input_schema: no_arguments_schema(),
+            output_schema: Some(json!({
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string" },
+                    "email": { "type": ["string", "null"] },
+                    "created_at": { "type": "string" },
+                    "user_metadata": { "type": "object" },
+                    "app_metadata": {
+                        "type": "object",
+                        "properties": {
+                            "provider": { "type": "string" },
+                            "providers": { "type": "array", "items": { "type": "string" } },
+                        },
+                        "required": ["provider", "providers"],
+                    },
+                },
+                "required": ["id", "created_at", "user_metadata", "app_metadata"],
+            })),
+        }
+    }
+
+    async fn call(&self, ctx: &RequestContext, arguments: Value) -> McpResult<CallToolResult> {
+        parse_arguments::<NoArguments>(arguments)?;
+
+        let profile = ctx.user_profile().await?;
+
+        structured(&profile)
+    }
+}
+
+#[derive(Serialize)]
+struct SessionStatus {
+    user_id: String,
+    email: Option<String>,
+    /// Unix timestamp (seconds) at which the access token expires
+    expires_at: u64,
+    /// Seconds until the access token expires
+    expires_in: u64,
+    /// Whether the session can be refreshed once the access token expires
+    refreshable: bool,
+    provider: Option<String>,
+    role: Option<String>,
+    aal: Option<String>,
+    scopes: Vec<String>,
+}
+
+/// Reports when the current session expires and what it grants
+struct SessionStatusTool;
+
+#[async_trait]
+impl Tool for SessionStatusTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "session_status".to_string(),
+            description: Some(
+                "Get the expiry, sign-in provider and scopes of the current Supabase session"
+                    .to_string(),
+            ),
+            input_schema: no_arguments_schema(),
+            output_schema: Some(json!({
+                "type": "object",
+                "properties": {
+                    "user_id": { "type": "string" },
+                    "email": { "type": ["string", "null"] },
+                    "expires_at": {
+                        "type": "integer",
+                        "description": "Unix timestamp (seconds) at which the access token expires",
+                    },
+                    "expires_in": {
+                        "type": "integer",
+                        "description": "Seconds until the access token expires",
+                    },
+                    "refreshable": { "type": "boolean" },
+                    "provider": { "type": ["string", "null"] },
+                    "role": { "type": ["string", "null"] },
+                    "aal": { "type": ["string", "null"] },
+                    "scopes": { "type": "array", "items": { "type": "string" } },
+                },
+                "required": ["user_id", "expires_at", "expires_in", "refreshable", "scopes"],
+            })),
+        }
+    }
+
+    async fn call(&self, ctx: &RequestContext, arguments: Value) -> McpResult<CallToolResult> {
+        parse_arguments::<NoArguments>(arguments)?;
+
+        let session = ctx.session().await?;
+        let claims = TokenValidator::new(ctx.config().clone()).decode(&session.access_token)?;
+
+        let status = SessionStatus {
+            user_id: claims.sub,
+            email: claims.email,
+            expires_at: claims.exp,
+            expires_in: claims.exp.saturating_sub(now_secs()),
+            refreshable: !session.refresh_token.is_empty(),
+            provider: claims.app_metadata["provider"].as_str().map(str::to_string),
+            role: claims.role,
+            aal: claims.aal,
+            scopes: claims
+                .scope
+                .map(|scope| scope.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+        };
+
+        structured(&status)
+    }
+}
+
+#[derive(Serialize)]
+struct Identities {
+    /// Provider the user first signed up with
+    primary: String,
+    providers: Vec<String>,
+}
+
+/// Lists the sign-in providers linked to the user's account
+struct ListIdentitiesTool;
+
+#[async_trait]
+impl Tool for ListIdentitiesTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_identities".to_string(),
+            description: Some(
+                "List the sign-in providers (GitHub and others) linked to the Supabase account"
+                    .to_string(),
+            ),
+            input_schema: no_arguments_schema(),
+            output_schema: Some(json!({
+                "type": "object",
+                "properties": {
+                    "primary": { "type": "string" },
+                    "providers": { "type": "array", "items": { "type": "string" } },
+                },
+                "required": ["primary", "providers"],
+            })),
+        }
+    }
+
+    async fn call(&self, ctx: &RequestContext, arguments: Value) -> McpResult<CallToolResult> {
+        parse_arguments::<NoArguments>(arguments)?;
+
+        let profile = ctx.user_profile().await?;
+
+        structured(&Identities {
+            primary: profile.app_metadata.provider,
+            providers: profile.app_metadata.providers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthHandler;
+    use crate::config::Config;
+    use crate::credentials::{CredentialStore, MemoryStore};
+    use crate::error::McpError;
+    use crate::session::Session;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// An access token for `user-1` of the project at `url`, signed with a secret nobody checks
+    fn access_token(url: &str, expires_at: u64) -> String {
+        let claims = json!({
+            "sub": "user-1",
+            "email": "user@example.com",
+            "iss": format!("{}/auth/v1", url),
+            "aud": "authenticated",
+            "exp": expires_at,
+            "iat": now_secs(),
+            "role": "authenticated",
+            "aal": "aal1",
+            "app_metadata": { "provider": "github" },
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap()
+    }
+
+    /// The local caller of a server for the project at `url`, with `stored` in its store
+    fn local_caller(url: &str, stored: Option<Session>) -> RequestContext {
+        let config = Config {
+            supabase_url: url.to_string(),
+            ..Config::default()
+        };
+        let store = MemoryStore::default();
+        if let Some(session) = stored {
+            store.save(&config.profile, &session).unwrap();
+        }
+        RequestContext::local(Arc::new(AuthHandler::with_store(config, Box::new(store))))
+    }
+
+    #[tokio::test]
+    async fn status_needs_a_stored_session() {
+        let ctx = local_caller("http://127.0.0.1:9", None);
+
+        let err = SessionStatusTool.call(&ctx, json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::NotAuthenticated), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn expired_sessions_without_a_refresh_token_are_reported_as_expired() {
+        let url = "http://127.0.0.1:9";
+        let stored = Session::from_access_token(access_token(url, now_secs() - 10));
+        let ctx = local_caller(
+            url,
+            Some(Session {
+                expires_at: now_secs() - 10,
+                ..stored
+            }),
+        );
+
+        let err = SessionStatusTool.call(&ctx, json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::SessionExpired), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn status_describes_the_refreshed_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // Metadata discovery finds nothing; the token endpoint hands out a new session
+        let refreshed = access_token(&url, now_secs() + 3600);
+        let session = json!({
+            "access_token": refreshed,
+            "refresh_token": "rotated",
+            "token_type": "bearer",
+            "expires_in": 3600,
+        })
+        .to_string();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut socket = BufReader::new(socket);
+                let mut request_line = String::new();
+                socket.read_line(&mut request_line).await.unwrap();
+
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    socket.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                socket.read_exact(&mut vec![0; length]).await.unwrap();
+
+                let response = if request_line.contains("grant_type=refresh_token") {
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        session.len(),
+                        session
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        .to_string()
+                };
+                socket
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let ctx = local_caller(
+            &url,
+            Some(Session {
+                access_token: access_token(&url, now_secs() + 10),
+                refresh_token: "spent".to_string(),
+                token_type: "bearer".to_string(),
+                expires_in: 3600,
+                expires_at: now_secs() + 10,
+            }),
+        );
+
+        let result = SessionStatusTool.call(&ctx, json!({})).await.unwrap();
+        let status = result.structured_content.unwrap();
+        assert_eq!(status["user_id"], "user-1");
+        assert_eq!(status["provider"], "github");
+        assert_eq!(status["refreshable"], true);
+        assert!(status["expires_in"].as_u64().unwrap() > 3000, "{}", status);
+        assert_eq!(ctx.session().await.unwrap().refresh_token, "rotated");
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::auth::AuthHandler;
+use crate::error::{McpError, McpResult, to_other_error};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::JsonRpcMessage;
+use crate::mcp::server::{McpServer, McpSession};
+
+/// Serve MCP over stdin/stdout, one JSON-RPC message per line
+///
+/// Tools run as the user signed in to the local profile. Runs until stdin closes or the
+/// client sends `shutdown`. Nothing else may write to stdout while this runs; diagnostics
+/// belong on stderr.
+pub async fn serve_stdio(server: Arc<McpServer>, auth: Arc<AuthHandler>) -> McpResult<()> {
+    let session = Arc::new(McpSession::default());
+    let ctx = Arc::new(RequestContext::local(auth));
+    let (tx, writer) = spawn_stdout_writer();
+    let notifier = tokio::spawn(relay_notifications(session.notifications(), tx.clone()));
+
+    let mut in_flight = JoinSet::new();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message = match JsonRpcMessage::parse(&line) {
+            Ok(message) => message,
+            Err(error) => {
+                let _ = tx.send(error);
+                continue;
+            }
+        };
+
+        // Tool calls can be slow, so they run concurrently; everything else is handled in
+        // order so lifecycle messages are processed before whatever follows them
+        let is_tool_call =
+            matches!(&message, JsonRpcMessage::Request(request) if request.method == "tools/call");
+
+        if is_tool_call {
+            let server = server.clone();
+            let session = session.clone();
+            let ctx = ctx.clone();
+            let tx = tx.clone();
+            in_flight.spawn(async move {
+                if let Some(response) = server.handle_message(&session, &ctx, message).await {
+                    let _ = tx.send(response);
+                }
+            });
+        } else if let Some(response) = server.handle_message(&session, &ctx, message).await {
+            let _ = tx.send(response);
+        }
+
+        if session.is_shut_down() {
+            break;
+        }
+    }
+
+    // Let running tool calls answer before the writer is closed
+    while in_flight.join_next().await.is_some() {}
+    notifier.abort();
+    drop(tx);
+
+    writer.await.map_err(to_other_error)?
+}
+
+/// Pass the session's server-initiated messages on to the stdout writer
+async fn relay_notifications(
+    mut notifications: broadcast::Receiver<JsonRpcMessage>,
+    tx: UnboundedSender<JsonRpcMessage>,
+) {
+    loop {
+        match notifications.recv().await {
+            Ok(message) => {
+                let _ = tx.send(message);
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Start the task that writes messages to stdout, one per line
+///
+/// A single writer keeps concurrent responses from interleaving. Dropping every sender ends
+/// the task.
+pub fn spawn_stdout_writer() -> (UnboundedSender<JsonRpcMessage>, JoinHandle<McpResult<()>>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<JsonRpcMessage>();
+
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let mut line = serde_json::to_string(&message)?;
+            line.push('\n');
+            stdout.write_all(line.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+        Ok::<_, McpError>(())
+    });
+
+    (tx, writer)
+}
@@ -0,0 +1,431 @@
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc, oneshot};
+
+use crate::auth::AuthHandler;
+use crate::error::{McpError, McpResult};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::JsonRpcMessage;
+use crate::mcp::server::McpSession;
+use crate::realtime::{ChannelOptions, PostgresChangesFilter, RealtimeClient, RealtimeEvent};
+
+/// Schema the REST API serves, and so the one table resources live in
+const SCHEMA: &str = "public";
+
+/// Feed key of the local profile's user; bearer callers are keyed by user ID
+const LOCAL_USER: &str = "";
+
+/// How often a feed refreshes the local token and forgets sessions that have ended
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// First wait before reconnecting a dropped feed
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+
+/// Longest wait before reconnecting a dropped feed
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
+/// Reconnects tried before a feed gives up
+const RECONNECT_ATTEMPTS: usize = 5;
+
+/// Resource subscriptions, fed by one Realtime connection per user
+///
+/// Each table with subscribers is a channel on its user's connection. Every database change
+/// on it sends `notifications/resources/updated` to the sessions subscribed to the table.
+/// Realtime applies row-level security, so users only hear about rows they can read.
+#[derive(Default)]
+pub struct Subscriptions {
+    feeds: Mutex<HashMap<String, mpsc::UnboundedSender<FeedCommand>>>,
+}
+
+/// Work for a feed task
+enum FeedCommand {
+    Subscribe {
+        table: String,
+        subscriber: Subscriber,
+        reply: oneshot::Sender<McpResult<()>>,
+    },
+    Unsubscribe {
+        uri: String,
+        session: Weak<McpSession>,
+    },
+    AccessToken(String),
+}
+
+/// A session waiting for changes to a resource
+struct Subscriber {
+    uri: String,
+    session: Weak<McpSession>,
+}
+
+impl Subscriber {
+    fn is(&self, uri: &str, session: &Weak<McpSession>) -> bool {
+        self.uri == uri && self.session.ptr_eq(session)
+    }
+}
+
+impl Subscriptions {
+    /// Notify `session` about `uri` whenever a row of `table` changes
+    pub async fn subscribe(
+        &self,
+        ctx: &RequestContext,
+        session: &Arc<McpSession>,
+        uri: &str,
+        table: &str,
+    ) -> McpResult<()> {
+        // A feed that is shutting down drops the request, so try once more on a fresh one
+        for _ in 0..2 {
+            let feed = self.feed(ctx).await?;
+            let (reply, response) = oneshot::channel();
+            let command = FeedCommand::Subscribe {
+                table: table.to_string(),
+                subscriber: Subscriber {
+                    uri: uri.to_string(),
+                    session: Arc::downgrade(session),
+                },
+                reply,
+            };
+
+            if feed.send(command).is_ok()
+                && let Ok(result) = response.await
+            {
+                return result;
+            }
+        }
+
+        Err(McpError::RealtimeError("Realtime feed closed".to_string()))
+    }
+
+    /// Stop notifying `session` about `uri`
+    pub async fn unsubscribe(&self, ctx: &RequestContext, session: &Arc<McpSession>, uri: &str) {
+        if let Some(feed) = self.feeds.lock().await.get(feed_key(ctx)) {
+            let _ = feed.send(FeedCommand::Unsubscribe {
+                uri: uri.to_string(),
+                session: Arc::downgrade(session),
+            });
+        }
+    }
+
+    /// Pass a bearer caller's current token on to their feed, which cannot refresh it itself
+    pub async fn update_token(&self, ctx: &RequestContext) {
+        if ctx.user_id().is_none() {
+            return;
+        }
+
+        if let Some(feed) = self.feeds.lock().await.get(feed_key(ctx))
+            && let Ok(token) = ctx.access_token().await
+        {
+            let _ = feed.send(FeedCommand::AccessToken(token));
+        }
+    }
+
+    /// The caller's feed, connecting one if they have none running
+    async fn feed(&self, ctx: &RequestContext) -> McpResult<mpsc::UnboundedSender<FeedCommand>> {
+        let key = feed_key(ctx);
+        if let Some(feed) = self.running_feed(key).await {
+            return Ok(feed);
+        }
+
+        // Connecting can take a while, and every request waits on the lock to update tokens
+        let token = ctx.access_token().await?;
+        let client = ctx.supabase().realtime(&token).await?;
+        let feed = Feed {
+            auth: ctx.auth().clone(),
+            local: ctx.user_id().is_none(),
+            access_token: token,
+            client,
+            tables: HashMap::new(),
+        };
+
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        {
+            let mut feeds = self.feeds.lock().await;
+            feeds.retain(|_, feed| !feed.is_closed());
+
+            // Another request may have connected one in the meantime
+            if let Some(running) = feeds.get(key).cloned() {
+                drop(feeds);
+                feed.client.close().await;
+                return Ok(running);
+            }
+            feeds.insert(key.to_string(), commands.clone());
+        }
+        tokio::spawn(feed.run(command_rx));
+
+        Ok(commands)
+    }
+
+    async fn running_feed(&self, key: &str) -> Option<mpsc::UnboundedSender<FeedCommand>> {
+        let mut feeds = self.feeds.lock().await;
+        feeds.retain(|_, feed| !feed.is_closed());
+        feeds.get(key).cloned()
+    }
+}
+
+fn feed_key(ctx: &RequestContext) -> &str {
+    ctx.user_id().unwrap_or(LOCAL_USER)
+}
+
+/// One user's Realtime connection and the subscribers of each table joined on it
+struct Feed {
+    auth: Arc<AuthHandler>,
+    /// Whether this is the local profile's feed, which refreshes its own token
+    local: bool,
+    access_token: String,
+    client: RealtimeClient,
+    tables: HashMap<String, Vec<Subscriber>>,
+}
+
+impl Feed {
+    /// Relay changes until every subscriber is gone or the connection cannot be restored
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<FeedCommand>) {
+        let mut maintenance = tokio::time::interval_at(
+            tokio::time::Instant::now() + MAINTENANCE_INTERVAL,
+            MAINTENANCE_INTERVAL,
+        );
+
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => self.handle(command).await,
+                    None => break,
+                },
+                event = self.client.next_event() => match event {
+                    Ok(event) => self.relay(event),
+                    Err(err) => {
+                        eprintln!("Realtime feed dropped: {}", err);
+                        if !self.reconnect().await {
+                            break;
+                        }
+                    }
+                },
+                _ = maintenance.tick() => {
+                    self.refresh_token().await;
+                    self.prune().await;
+                    if self.tables.is_empty() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Refuse further commands so the next subscriber gets a new feed
+        commands.close();
+        self.client.close().await;
+    }
+
+    async fn handle(&mut self, command: FeedCommand) {
+        match command {
+            FeedCommand::Subscribe {
+                table,
+                subscriber,
+                reply,
+            } => {
+                let _ = reply.send(self.subscribe(table, subscriber).await);
+            }
+            FeedCommand::Unsubscribe { uri, session } => {
+                for subscribers in self.tables.values_mut() {
+                    subscribers.retain(|subscriber| !subscriber.is(&uri, &session));
+                }
+                self.prune().await;
+            }
+            FeedCommand::AccessToken(token) => {
+                let _ = self.client.set_access_token(&token);
+                self.access_token = token;
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, table: String, subscriber: Subscriber) -> McpResult<()> {
+        if !self.tables.contains_key(&table) {
+            self.client
+                .join(&channel_name(&table), &channel_options(&table))
+                .await?;
+        }
+
+        let subscribers = self.tables.entry(table).or_default();
+        subscribers.retain(|existing| !existing.is(&subscriber.uri, &subscriber.session));
+        subscribers.push(subscriber);
+        Ok(())
+    }
+
+    fn relay(&mut self, event: RealtimeEvent) {
+        match event {
+            RealtimeEvent::PostgresChanges { change, .. } => {
+                let Some(subscribers) = self.tables.get(&change.table) else {
+                    return;
+                };
+
+                for subscriber in subscribers {
+                    if let Some(session) = subscriber.session.upgrade() {
+                        session.notify(JsonRpcMessage::notification(
+                            "notifications/resources/updated",
+                            json!({ "uri": subscriber.uri }),
+                        ));
+                    }
+                }
+            }
+            RealtimeEvent::System { channel, payload } if payload["status"] == "error" => {
+                eprintln!("Realtime channel {}: {}", channel, payload["message"]);
+            }
+            // Its subscribers hear nothing more, so forget them; subscribing again rejoins
+            RealtimeEvent::ChannelClosed { channel, reason } => {
+                eprintln!("Realtime channel {} closed: {}", channel, reason);
+                self.tables
+                    .retain(|table, _| channel_name(table) != channel);
+            }
+            _ => {}
+        }
+    }
+
+    /// Forget subscribers whose session has ended and leave tables nobody watches
+    async fn prune(&mut self) {
+        let mut unused = Vec::new();
+        for (table, subscribers) in &mut self.tables {
+            subscribers.retain(|subscriber| subscriber.session.strong_count() > 0);
+            if subscribers.is_empty() {
+                unused.push(table.clone());
+            }
+        }
+
+        for table in unused {
+            self.tables.remove(&table);
+            let _ = self.client.leave(&channel_name(&table)).await;
+        }
+    }
+
+    async fn refresh_token(&mut self) {
+        if !self.local {
+            return;
+        }
+
+        match self.auth.get_token().await {
+            Ok(token) => {
+                let _ = self.client.set_access_token(&token);
+                self.access_token = token;
+            }
+            Err(err) => eprintln!("Could not refresh the Realtime token: {}", err),
+        }
+    }
+
+    /// Open a new connection and rejoin every table; false once that keeps failing
+    async fn reconnect(&mut self) -> bool {
+        let mut delay = RECONNECT_DELAY_MIN;
+
+        for _ in 0..RECONNECT_ATTEMPTS {
+            self.prune().await;
+            if self.tables.is_empty() {
+                return false;
+            }
+
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+
+            match self.connect().await {
+                Ok(()) => return true,
+                Err(err) => eprintln!("Could not reconnect the Realtime feed: {}", err),
+            }
+        }
+
+        false
+    }
+
+    async fn connect(&mut self) -> McpResult<()> {
+        self.refresh_token().await;
+
+        let client = self.auth.supabase().realtime(&self.access_token).await?;
+        for table in self.tables.keys() {
+            client
+                .join(&channel_name(table), &channel_options(table))
+                .await?;
+        }
+
+        std::mem::replace(&mut self.client, client).close().await;
+        Ok(())
+    }
+}
+
+fn channel_name(table: &str) -> String {
+    format!("table:{}", table)
+}
+
+/// Every change to the rows of `table`
+fn channel_options(table: &str) -> ChannelOptions {
+    ChannelOptions {
+        postgres_changes: vec![PostgresChangesFilter {
+            event: "*".to_string(),
+            schema: SCHEMA.to_string(),
+            table: Some(table.to_string()),
+            filter: None,
+        }],
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::credentials::CredentialStoreKind;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn a_hanging_connect_does_not_hold_up_other_requests() {
+        // A Realtime endpoint that accepts the connection but never answers the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = Config {
+            supabase_url: format!("http://{}", listener.local_addr().unwrap()),
+            credential_store: CredentialStoreKind::Env,
+            ..Config::default()
+        };
+        let user = serde_json::from_value(json!({
+            "id": "user-1",
+            "email": null,
+            "user_metadata": {},
+            "app_metadata": { "provider": "github", "providers": ["github"] },
+            "created_at": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        let ctx = Arc::new(RequestContext::bearer(
+            Arc::new(AuthHandler::new(config)),
+            "token".to_string(),
+            user,
+        ));
+
+        let subscriptions = Arc::new(Subscriptions::default());
+        let subscribing = tokio::spawn({
+            let subscriptions = subscriptions.clone();
+            let ctx = ctx.clone();
+            async move {
+                let session = Arc::new(McpSession::default());
+                let uri = "supabase://table/todos";
+                subscriptions.subscribe(&ctx, &session, uri, "todos").await
+            }
+        });
+        let _connection = listener.accept().await.unwrap();
+
+        let update = subscriptions.update_token(&ctx);
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), update)
+                .await
+                .is_ok()
+        );
+        subscribing.abort();
+    }
+
+    #[test]
+    fn each_table_has_its_own_channel_for_every_change() {
+        assert_eq!(channel_name("todos"), "table:todos");
+
+        let options = channel_options("todos");
+        let [filter] = options.postgres_changes.as_slice() else {
+            panic!("expected one filter, got {:?}", options.postgres_changes);
+        };
+        assert_eq!(filter.event, "*");
+        assert_eq!(filter.schema, SCHEMA);
+        assert_eq!(filter.table.as_deref(), Some("todos"));
+        assert_eq!(filter.filter, None);
+    }
+}
@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use serde_json::Value;
+use url::Url;
+
+use crate::error::{McpError, McpResult};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::{Resource, ResourceContents, ResourceTemplate};
+use crate::mcp::resources::{ResourceProvider, ResourceRegistry};
+
+/// URI scheme of resources backed by the Supabase project
+const SCHEME: &str = "supabase";
+/// Rows returned when the URI sets no `limit`
+const DEFAULT_LIMIT: usize = 100;
+const MIME_TYPE: &str = "application/json";
+
+/// Register the provider that exposes database tables as resources
+pub fn register(registry: &mut ResourceRegistry) {
+    registry.register(Box::new(TableResources));
+}
+
+/// Exposes the tables and views of the REST API as `supabase://table/<name>`
+struct TableResources;
+
+fn table_uri(name: &str) -> String {
+    format!(
+        "{}://table/{}",
+        SCHEME,
+        utf8_percent_encode(name, NON_ALPHANUMERIC)
+    )
+}
+
+#[async_trait]
+impl ResourceProvider for TableResources {
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        vec![ResourceTemplate {
+            uri_template: format!("{}://table/{{name}}{{?select,limit}}", SCHEME),
+            name: "table".to_string(),
+            description: Some(format!(
+                "Rows of a Supabase table, visible under row-level security. `select` takes \
+                 PostgREST column syntax; `limit` defaults to {}.",
+                DEFAULT_LIMIT
+            )),
+            mime_type: Some(MIME_TYPE.to_string()),
+        }]
+    }
+
+    async fn list(&self, ctx: &RequestContext) -> McpResult<Vec<Resource>> {
+        let token = ctx.access_token().await?;
+        let tables = ctx.supabase().list_tables(&token).await?;
+
+        Ok(tables
+            .into_iter()
+            .map(|table| Resource {
+                uri: table_uri(&table.name),
+                name: table.name,
+                description: table.description,
+                mime_type: Some(MIME_TYPE.to_string()),
+            })
+            .collect())
+    }
+
+    async fn read(&self, ctx: &RequestContext, uri: &Url) -> McpResult<Option<ResourceContents>> {
+        let Some(name) = table_name(uri)? else {
+            return Ok(None);
+        };
+
+        let mut select = "*".to_string();
+        let mut limit = DEFAULT_LIMIT;
+        for (key, value) in uri.query_pairs() {
+            match key.as_ref() {
+                "select" => select = value.into_owned(),
+                "limit" => {
+                    limit = value.parse().map_err(|_| {
+                        McpError::InvalidArguments(format!("limit must be a number, got {}", value))
+                    })?
+                }
+                other => {
+                    return Err(McpError::InvalidArguments(format!(
+                        "Unsupported query parameter {}; expected select or limit",
+                        other
+                    )));
+                }
+            }
+        }
+
+        // The caller's own token, so the rows are those row-level security lets them see
+        let token = ctx.access_token().await?;
+        let rows: Vec<Value> = ctx
+            .supabase()
+            .from(&name)
+            .select(&select)
+            .limit(limit)
+            .execute(&token)
+            .await?
+            .data;
+
+        Ok(Some(ResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some(MIME_TYPE.to_string()),
+            text: serde_json::to_string_pretty(&rows)?,
+        }))
+    }
+
+    fn watched_table(&self, uri: &Url) -> McpResult<Option<String>> {
+        table_name(uri)
+    }
+}
+
+/// The table a `supabase://table/<name>` URI names, or `None` for other URIs
+fn table_name(uri: &Url) -> McpResult<Option<String>> {
+    if uri.scheme() != SCHEME || uri.host_str() != Some("table") {
+        return Ok(None);
+    }
+
+    let name = uri.path().trim_start_matches('/');
+    if name.is_empty() || name.contains('/') {
+        return Err(McpError::ResourceNotFound(uri.to_string()));
+    }
+
+    percent_decode_str(name)
+        .decode_utf8()
+        .map(|name| Some(name.into_owned()))
+        .map_err(|_| McpError::ResourceNotFound(uri.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_uris_round_trip() {
+        let uri = Url::parse(&table_uri("order items")).unwrap();
+        assert_eq!(uri.as_str(), "supabase://table/order%20items");
+        assert_eq!(table_name(&uri).unwrap().as_deref(), Some("order items"));
+    }
+
+    #[test]
+    fn other_uris_are_not_tables() {
+        let bucket = Url::parse("supabase://storage/avatars").unwrap();
+        assert_eq!(table_name(&bucket).unwrap(), None);
+
+        let nested = Url::parse("supabase://table/a/b").unwrap();
+        assert!(matches!(
+            table_name(&nested),
+            Err(McpError::ResourceNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{McpError, McpResult};
+use crate::mcp::context::RequestContext;
+use crate::mcp::protocol::{CallToolResult, ToolDefinition};
+
+/// A tool the MCP server exposes through `tools/list` and `tools/call`
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Name, description and schemas advertised to clients
+    fn definition(&self) -> ToolDefinition;
+
+    /// Run the tool with the caller's arguments, as the caller
+    async fn call(&self, ctx: &RequestContext, arguments: Value) -> McpResult<CallToolResult>;
+}
+
+/// The set of tools a server exposes
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// Add a tool to the registry
+    pub fn register(&mut self, tool: Box<dyn Tool>) {
+        self.tools.push(tool);
+    }
+
+    /// Definitions of all registered tools
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|tool| tool.definition()).collect()
+    }
+
+    /// Find a tool by name
+    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|tool| tool.definition().name == name)
+            .map(Box::as_ref)
+    }
+}
+
+/// Deserialize tool arguments; a missing `arguments` object counts as empty
+pub fn parse_arguments<T: for<'de> Deserialize<'de>>(arguments: Value) -> McpResult<T> {
+    let arguments = match arguments {
+        Value::Null => Value::Object(Default::default()),
+        arguments => arguments,
+    };
+    serde_json::from_value(arguments).map_err(|e| McpError::InvalidArguments(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Arguments {
+        #[serde(default)]
+        limit: Option<u32>,
+    }
+
+    #[test]
+    fn missing_arguments_count_as_empty() {
+        let arguments: Arguments = parse_arguments(Value::Null).unwrap();
+        assert_eq!(arguments.limit, None);
+
+        let arguments: Arguments = parse_arguments(json!({ "limit": 5 })).unwrap();
+        assert_eq!(arguments.limit, Some(5));
+    }
+
+    #[test]
+    fn malformed_arguments_are_invalid() {
+        let result = parse_arguments::<Arguments>(json!({ "limit": "five" }));
+        assert!(matches!(result, Err(McpError::InvalidArguments(_))));
+    }
+}
@@ -0,0 +1,398 @@
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{McpError, McpResult};
+use crate::supabase::SupabaseClient;
+
+/// A table or view exposed through the REST API
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Error body PostgREST returns with failed requests
+#[derive(Deserialize)]
+struct PostgrestError {
+    message: String,
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    details: Option<String>,
+    #[serde(default)]
+    hint: Option<String>,
+}
+
+impl SupabaseClient {
+    /// Tables and views the owner of `token` can reach, from the REST API's OpenAPI document
+    pub async fn list_tables(&self, token: &str) -> McpResult<Vec<TableInfo>> {
+        let response = self
+            .rest_request(Method::GET, "", token)
+            .header("Accept", "application/openapi+json")
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        let document: Value = check_response(response)
+            .await?
+            .json()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        // PostgREST describes every exposed table and view under `definitions`
+        let tables = document["definitions"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(name, definition)| TableInfo {
+                name: name.clone(),
+                description: definition["description"].as_str().map(str::to_string),
+            })
+            .collect();
+
+        Ok(tables)
+    }
+
+    /// Start a query against `table`; without an operation it selects all columns
+    pub fn from(&self, table: &str) -> QueryBuilder<'_> {
+        QueryBuilder::new(
+            self,
+            utf8_percent_encode(table, NON_ALPHANUMERIC).to_string(),
+        )
+    }
+
+    /// Call the database function `function` with named arguments
+    pub fn rpc<A: Serialize>(&self, function: &str, args: &A) -> McpResult<QueryBuilder<'_>> {
+        let path = format!("rpc/{}", utf8_percent_encode(function, NON_ALPHANUMERIC));
+
+        let mut builder = QueryBuilder::new(self, path);
+        builder.method = Method::POST;
+        builder.body = Some(serde_json::to_value(args)?);
+        Ok(builder)
+    }
+}
+
+/// A PostgREST request against one table, view or function
+pub struct QueryBuilder<'a> {
+    supabase: &'a SupabaseClient,
+    path: String,
+    method: Method,
+    params: Vec<(String, String)>,
+    order: Vec<String>,
+    range: Option<(u64, u64)>,
+    prefer: Vec<&'static str>,
+    body: Option<Value>,
+}
+
+impl<'a> QueryBuilder<'a> {
+    fn new(supabase: &'a SupabaseClient, path: String) -> Self {
+        Self {
+            supabase,
+            path,
+            method: Method::GET,
+            params: Vec::new(),
+            order: Vec::new(),
+            range: None,
+            prefer: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Choose the columns to return, in PostgREST `select` syntax
+    pub fn select(mut self, columns: &str) -> Self {
+        self.params
+            .push(("select".to_string(), columns.to_string()));
+        self
+    }
+
+    /// Insert one row (an object) or many (an array)
+    pub fn insert<T: Serialize>(self, rows: &T) -> McpResult<Self> {
+        self.write(Method::POST, rows)
+    }
+
+    /// Insert rows, updating those whose primary key already exists
+    pub fn upsert<T: Serialize>(self, rows: &T) -> McpResult<Self> {
+        let mut builder = self.write(Method::POST, rows)?;
+        builder.prefer.push("resolution=merge-duplicates");
+        Ok(builder)
+    }
+
+    /// Resolve upsert conflicts on these unique columns instead of the primary key
+    pub fn on_conflict(mut self, columns: &str) -> Self {
+        self.params
+            .push(("on_conflict".to_string(), columns.to_string()));
+        self
+    }
+
+    fn write<T: Serialize>(mut self, method: Method, body: &T) -> McpResult<Self> {
+        self.method = method;
+        self.body = Some(serde_json::to_value(body)?);
+        Ok(self)
+    }
+
+    /// Add a raw filter such as `eq.42`, `not.is.null` or `in.(1,2)` on `column`
+    pub fn filter(mut self, column: &str, condition: &str) -> Self {
+        self.params
+            .push((column.to_string(), condition.to_string()));
+        self
+    }
+
+    /// Sort by `column`; later calls break ties of earlier ones
+    pub fn order(mut self, column: &str, ascending: bool) -> Self {
+        let direction = if ascending { "asc" } else { "desc" };
+        self.order.push(format!("{}.{}", column, direction));
+        self
+    }
+
+    /// Return at most `count` rows
+    pub fn limit(mut self, count: usize) -> Self {
+        self.params.push(("limit".to_string(), count.to_string()));
+        self
+    }
+
+    /// Return rows `from` through `to` (zero-based, inclusive) through the `Range` header
+    pub fn range(mut self, from: u64, to: u64) -> Self {
+        self.range = Some((from, to));
+        self
+    }
+
+    /// Have writes return the affected rows instead of an empty body
+    pub fn return_representation(mut self) -> Self {
+        self.prefer.push("return=representation");
+        self
+    }
+
+    /// Have the server count all matching rows and report the total in `Content-Range`
+    pub fn exact_count(mut self) -> Self {
+        self.prefer.push("count=exact");
+        self
+    }
+
+    /// Send the request as the owner of `token`, so row-level security applies
+    ///
+    /// `T` is what the body deserializes into: usually `Vec<Row>`, or `()` for writes that
+    /// return nothing.
+    pub async fn execute<T: DeserializeOwned>(self, token: &str) -> McpResult<QueryResponse<T>> {
+        let request = self.into_request(token);
+        let response = check_response(request.send().await.map_err(McpError::HttpError)?).await?;
+
+        let content_range = response
+            .headers()
+            .get("Content-Range")
+            .and_then(|value| value.to_str().ok())
+            .and_then(ContentRange::parse);
+
+        let status = response.status();
+        let body = response.bytes().await.map_err(McpError::HttpError)?;
+        // Writes without `return=representation` answer with an empty body
+        let data = if status == StatusCode::NO_CONTENT || body.is_empty() {
+            serde_json::from_value(Value::Null)?
+        } else {
+            serde_json::from_slice(&body)?
+        };
+
+        Ok(QueryResponse {
+            data,
+            content_range,
+        })
+    }
+
+    fn into_request(self, token: &str) -> RequestBuilder {
+        let mut params = self.params;
+        if !self.order.is_empty() {
+            params.push(("order".to_string(), self.order.join(",")));
+        }
+
+        let mut request = self
+            .supabase
+            .rest_request(self.method, &self.path, token)
+            .query(&params);
+        if let Some((from, to)) = self.range {
+            request = request
+                .header("Range-Unit", "items")
+                .header("Range", format!("{}-{}", from, to));
+        }
+        if !self.prefer.is_empty() {
+            request = request.header("Prefer", self.prefer.join(","));
+        }
+        if let Some(body) = &self.body {
+            request = request.json(body);
+        }
+
+        request
+    }
+}
+
+/// The body of a PostgREST response and the rows it covers
+#[derive(Debug)]
+pub struct QueryResponse<T> {
+    pub data: T,
+    pub content_range: Option<ContentRange>,
+}
+
+/// A parsed `Content-Range` header, such as `0-24/3573` or `*/0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// First and last row returned, zero-based and inclusive; `None` when no rows came back
+    pub rows: Option<(u64, u64)>,
+    /// Total number of matching rows, when counted
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    fn parse(header: &str) -> Option<Self> {
+        let (rows, total) = header.split_once('/')?;
+
+        let rows = match rows {
+            "*" => None,
+            rows => {
+                let (first, last) = rows.split_once('-')?;
+                Some((first.parse().ok()?, last.parse().ok()?))
+            }
+        };
+        let total = match total {
+            "*" => None,
+            total => Some(total.parse().ok()?),
+        };
+
+        Some(Self { rows, total })
+    }
+}
+
+/// Turn a failed PostgREST response into a `DatabaseError` with the server's explanation
+async fn check_response(response: Response) -> McpResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<PostgrestError>(&body) {
+        Ok(error) => {
+            let mut message = error.message;
+            if let Some(code) = error.code {
+                message = format!("{} ({})", message, code);
+            }
+            if let Some(details) = error.details {
+                message = format!("{}: {}", message, details);
+            }
+            if let Some(hint) = error.hint {
+                message = format!("{}. Hint: {}", message, hint);
+            }
+            message
+        }
+        Err(_) => format!("HTTP {}", status),
+    };
+
+    Err(McpError::DatabaseError(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn client() -> SupabaseClient {
+        SupabaseClient::new(Config {
+            supabase_url: "https://project.supabase.co".to_string(),
+            supabase_anon_key: "anon-key".to_string(),
+            ..Config::default()
+        })
+    }
+
+    fn build(query: QueryBuilder<'_>) -> reqwest::Request {
+        query.into_request("user-token").build().unwrap()
+    }
+
+    fn header<'r>(request: &'r reqwest::Request, name: &str) -> Option<&'r str> {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    #[test]
+    fn parses_content_range() {
+        assert_eq!(
+            ContentRange::parse("0-24/3573"),
+            Some(ContentRange {
+                rows: Some((0, 24)),
+                total: Some(3573),
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("*/0"),
+            Some(ContentRange {
+                rows: None,
+                total: Some(0),
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("*/*"),
+            Some(ContentRange {
+                rows: None,
+                total: None,
+            })
+        );
+        assert_eq!(ContentRange::parse("0-24"), None);
+        assert_eq!(ContentRange::parse("a-b/3"), None);
+    }
+
+    #[test]
+    fn encodes_query() {
+        let supabase = client();
+        let query = supabase
+            .from("my table")
+            .select("id,name")
+            .filter("name", "like.a&b*")
+            .order("created_at", false)
+            .order("id", true)
+            .range(0, 24)
+            .exact_count();
+        let request = build(query);
+
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.url().path(), "/rest/v1/my%20table");
+        assert_eq!(
+            request.url().query(),
+            Some("select=id%2Cname&name=like.a%26b*&order=created_at.desc%2Cid.asc")
+        );
+        assert_eq!(header(&request, "Range"), Some("0-24"));
+        assert_eq!(header(&request, "Range-Unit"), Some("items"));
+        assert_eq!(header(&request, "Prefer"), Some("count=exact"));
+        assert_eq!(header(&request, "apikey"), Some("anon-key"));
+        assert_eq!(header(&request, "Authorization"), Some("Bearer user-token"));
+    }
+
+    #[test]
+    fn encodes_writes_and_rpc() {
+        let supabase = client();
+        let row = serde_json::json!({ "id": 1 });
+
+        let upsert = build(
+            supabase
+                .from("tasks")
+                .upsert(&row)
+                .unwrap()
+                .on_conflict("id")
+                .return_representation(),
+        );
+        assert_eq!(upsert.method(), Method::POST);
+        assert_eq!(upsert.url().query(), Some("on_conflict=id"));
+        assert_eq!(
+            header(&upsert, "Prefer"),
+            Some("resolution=merge-duplicates,return=representation")
+        );
+
+        let rpc = build(supabase.rpc("add/one", &row).unwrap());
+        assert_eq!(rpc.method(), Method::POST);
+        assert_eq!(rpc.url().path(), "/rest/v1/rpc/add%2Fone");
+        assert_eq!(
+            rpc.body().and_then(|body| body.as_bytes()),
+            Some(&b"{\"id\":1}"[..])
+        );
+    }
+}
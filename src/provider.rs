@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::error::{McpError, McpResult};
+
+/// Provider used when none is named
+pub const DEFAULT_PROVIDER: &str = "github";
+
+/// Providers supported by Supabase Auth: name, display name and default scopes
+const KNOWN_PROVIDERS: &[(&str, &str, Option<&str>)] = &[
+    ("apple", "Apple", None),
+    ("azure", "Azure", Some("email")),
+    ("bitbucket", "Bitbucket", Some("email")),
+    ("discord", "Discord", None),
+    ("email", "Email", None),
+    ("facebook", "Facebook", None),
+    ("figma", "Figma", None),
+    ("github", "GitHub", Some("user:email")),
+    ("gitlab", "GitLab", Some("read_user")),
+    ("google", "Google", None),
+    ("kakao", "Kakao", None),
+    ("keycloak", "Keycloak", Some("openid")),
+    ("linkedin_oidc", "LinkedIn", None),
+    ("notion", "Notion", None),
+    ("phone", "Phone", None),
+    ("slack_oidc", "Slack", None),
+    ("spotify", "Spotify", None),
+    ("twitch", "Twitch", None),
+    ("twitter", "Twitter", None),
+    ("workos", "WorkOS", None),
+    ("zoom", "Zoom", None),
+];
+
+/// Parameters the CLI sets on authorization URLs itself, which `query_params` may not override
+const RESERVED_QUERY_PARAMS: &[&str] = &[
+    "client_id",
+    "code_challenge",
+    "code_challenge_method",
+    "flow_type",
+    "provider",
+    "redirect_to",
+    "scopes",
+    "skip_http_redirect",
+    "state",
+];
+
+/// An OAuth provider enabled on the Supabase project, and what to ask it for
+#[derive(Debug, Clone)]
+pub struct OAuthProvider {
+    pub name: String,
+    /// Space-separated scopes, replacing the provider's defaults
+    pub scopes: Option<String>,
+    /// Extra parameters passed on to the provider's authorization page
+    pub query_params: BTreeMap<String, String>,
+}
+
+impl OAuthProvider {
+    /// The provider called `name`, with the profile's settings for it over the defaults
+    pub fn new(name: &str, config: &Config) -> McpResult<Self> {
+        let name = name.to_ascii_lowercase();
+        let settings = config.oauth_providers.get(&name);
+
+        if let Some(key) = settings.and_then(|settings| {
+            settings
+                .query_params
+                .keys()
+                .find(|key| RESERVED_QUERY_PARAMS.contains(&key.as_str()))
+        }) {
+            return Err(McpError::ConfigError(format!(
+                "query_params of provider {} cannot set {}, which the CLI sets itself",
+                name, key
+            )));
+        }
+
+        let default_scopes = KNOWN_PROVIDERS
+            .iter()
+            .find(|(known, ..)| *known == name)
+            .and_then(|(_, _, scopes)| *scopes);
+
+        Ok(Self {
+            scopes: settings
+                .and_then(|settings| settings.scopes.clone())
+                .or_else(|| default_scopes.map(str::to_string)),
+            query_params: settings
+                .map(|settings| settings.query_params.clone())
+                .unwrap_or_default(),
+            name,
+        })
+    }
+
+    pub fn display_name(&self) -> String {
+        display_name(&self.name)
+    }
+}
+
+/// Human-readable name of a provider, such as `GitHub` for `github`
+pub fn display_name(provider: &str) -> String {
+    if let Some((_, display, _)) = KNOWN_PROVIDERS.iter().find(|(name, ..)| *name == provider) {
+        return display.to_string();
+    }
+
+    // Custom providers: capitalize the name
+    let mut chars = provider.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderSettings;
+
+    fn config_with(name: &str, settings: ProviderSettings) -> Config {
+        Config {
+            oauth_providers: BTreeMap::from([(name.to_string(), settings)]),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn configured_scopes_replace_the_defaults() {
+        let provider = OAuthProvider::new("GitHub", &Config::default()).unwrap();
+        assert_eq!(provider.name, "github");
+        assert_eq!(provider.scopes.as_deref(), Some("user:email"));
+
+        let settings = ProviderSettings {
+            scopes: Some("read:org".to_string()),
+            ..ProviderSettings::default()
+        };
+        let provider = OAuthProvider::new("github", &config_with("github", settings)).unwrap();
+        assert_eq!(provider.scopes.as_deref(), Some("read:org"));
+    }
+
+    #[test]
+    fn reserved_query_params_are_rejected() {
+        for key in ["redirect_to", "code_challenge", "provider"] {
+            let settings = ProviderSettings {
+                query_params: BTreeMap::from([(key.to_string(), "x".to_string())]),
+                ..ProviderSettings::default()
+            };
+            assert!(matches!(
+                OAuthProvider::new("google", &config_with("google", settings)),
+                Err(McpError::ConfigError(_))
+            ));
+        }
+
+        let settings = ProviderSettings {
+            query_params: BTreeMap::from([("prompt".to_string(), "consent".to_string())]),
+            ..ProviderSettings::default()
+        };
+        let provider = OAuthProvider::new("google", &config_with("google", settings)).unwrap();
+        assert_eq!(provider.query_params["prompt"], "consent");
+    }
+
+    #[test]
+    fn display_names() {
+        assert_eq!(display_name("linkedin_oidc"), "LinkedIn");
+        assert_eq!(display_name("custom"), "Custom");
+        assert_eq!(display_name(""), "");
+    }
+}
@@ -0,0 +1,606 @@
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+use crate::error::{McpError, McpResult};
+use crate::supabase::SupabaseClient;
+
+/// Phoenix serializer version the client speaks
+const PROTOCOL_VERSION: &str = "1.0.0";
+/// How often the server is sent a heartbeat; it drops sockets that stay silent for 60s
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+/// How long to wait for the server to answer a join or leave
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One Phoenix channel message
+#[derive(Debug, Serialize, Deserialize)]
+struct PhoenixMessage {
+    topic: String,
+    event: String,
+    payload: Value,
+    #[serde(rename = "ref", default)]
+    reference: Option<String>,
+    #[serde(default)]
+    join_ref: Option<String>,
+}
+
+/// Which database changes a channel subscribes to
+#[derive(Debug, Clone, Serialize)]
+pub struct PostgresChangesFilter {
+    /// `INSERT`, `UPDATE`, `DELETE` or `*`
+    pub event: String,
+    pub schema: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    /// Row filter in PostgREST syntax, such as `room_id=eq.42`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+/// What a channel listens to when joined
+#[derive(Debug, Clone, Default)]
+pub struct ChannelOptions {
+    pub postgres_changes: Vec<PostgresChangesFilter>,
+    /// Also receive the broadcasts this client sends
+    pub broadcast_self: bool,
+    /// Key this client's presence is tracked under; the server picks one when empty
+    pub presence_key: String,
+    /// Authorize the channel with Realtime's RLS policies
+    pub private: bool,
+}
+
+/// A database change delivered through `postgres_changes`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostgresChange {
+    pub schema: String,
+    pub table: String,
+    /// `INSERT`, `UPDATE` or `DELETE`
+    #[serde(rename(deserialize = "type"))]
+    pub event: String,
+    #[serde(default)]
+    pub commit_timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_record: Option<Value>,
+}
+
+/// Something the server pushed on a joined channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RealtimeEvent {
+    PostgresChanges {
+        channel: String,
+        #[serde(flatten)]
+        change: PostgresChange,
+    },
+    Broadcast {
+        channel: String,
+        event: String,
+        payload: Value,
+    },
+    /// Everyone present when the channel was joined
+    PresenceState { channel: String, state: Value },
+    PresenceDiff {
+        channel: String,
+        joins: Value,
+        leaves: Value,
+    },
+    /// Status reports, such as the result of subscribing to database changes
+    System { channel: String, payload: Value },
+    /// The server closed the channel or it crashed
+    ChannelClosed { channel: String, reason: String },
+}
+
+/// Work for the connection task
+enum Command {
+    Push {
+        topic: String,
+        event: String,
+        payload: Value,
+        reply: Option<oneshot::Sender<McpResult<Value>>>,
+    },
+    AccessToken(String),
+}
+
+/// A connection to Supabase Realtime, speaking the Phoenix channel protocol
+///
+/// A background task owns the socket, sends heartbeats and hands pushed events to
+/// [`RealtimeClient::next_event`].
+pub struct RealtimeClient {
+    commands: mpsc::UnboundedSender<Command>,
+    events: mpsc::UnboundedReceiver<RealtimeEvent>,
+    /// `None` once the connection has ended and its outcome was reported
+    task: Option<JoinHandle<McpResult<()>>>,
+    access_token: String,
+}
+
+impl SupabaseClient {
+    /// Open a Realtime connection authorized with `token`
+    pub async fn realtime(&self, token: &str) -> McpResult<RealtimeClient> {
+        RealtimeClient::connect(self, token).await
+    }
+}
+
+impl RealtimeClient {
+    async fn connect(supabase: &SupabaseClient, token: &str) -> McpResult<Self> {
+        let mut url = Url::parse(&supabase.api_url("realtime/v1/websocket"))
+            .map_err(|e| McpError::RealtimeError(format!("Invalid Realtime URL: {}", e)))?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        let _ = url.set_scheme(scheme);
+        url.query_pairs_mut()
+            .append_pair("apikey", supabase.anon_key())
+            .append_pair("vsn", PROTOCOL_VERSION);
+
+        let (socket, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .map_err(|e| McpError::RealtimeError(format!("Could not connect: {}", e)))?;
+
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (event_tx, events) = mpsc::unbounded_channel();
+        let task = tokio::spawn(run_connection(socket, command_rx, event_tx));
+
+        Ok(Self {
+            commands,
+            events,
+            task: Some(task),
+            access_token: token.to_string(),
+        })
+    }
+
+    /// Join `channel`, waiting until the server accepts the subscription
+    pub async fn join(&self, channel: &str, options: &ChannelOptions) -> McpResult<()> {
+        let payload = json!({
+            "config": {
+                "broadcast": { "self": options.broadcast_self, "ack": false },
+                "presence": { "key": options.presence_key },
+                "postgres_changes": options.postgres_changes,
+                "private": options.private,
+            },
+            "access_token": self.access_token,
+        });
+
+        self.request(channel, "phx_join", payload).await?;
+        Ok(())
+    }
+
+    /// Leave `channel`
+    pub async fn leave(&self, channel: &str) -> McpResult<()> {
+        self.request(channel, "phx_leave", json!({})).await?;
+        Ok(())
+    }
+
+    /// Send a broadcast message to everyone on `channel`
+    pub fn broadcast(&self, channel: &str, event: &str, payload: Value) -> McpResult<()> {
+        let payload = json!({ "type": "broadcast", "event": event, "payload": payload });
+        self.push(channel, "broadcast", payload, None)
+    }
+
+    /// Announce this client's presence on `channel` with `state`
+    pub fn track(&self, channel: &str, state: Value) -> McpResult<()> {
+        let payload = json!({ "type": "presence", "event": "track", "payload": state });
+        self.push(channel, "presence", payload, None)
+    }
+
+    /// Switch every joined channel, and later joins, to a refreshed access token
+    pub fn set_access_token(&mut self, token: &str) -> McpResult<()> {
+        if token == self.access_token {
+            return Ok(());
+        }
+
+        self.access_token = token.to_string();
+        self.send(Command::AccessToken(token.to_string()))
+    }
+
+    /// Wait for the next event on any joined channel
+    ///
+    /// Fails once the connection is lost. Cancel-safe, so it can be used in `select!`.
+    pub async fn next_event(&mut self) -> McpResult<RealtimeEvent> {
+        if let Some(event) = self.events.recv().await {
+            return Ok(event);
+        }
+
+        // The connection task has ended; report why, once
+        match self.task.take() {
+            Some(task) => match task.await {
+                Ok(Err(err)) => Err(err),
+                _ => Err(McpError::RealtimeError("Connection closed".to_string())),
+            },
+            None => Err(McpError::RealtimeError("Connection closed".to_string())),
+        }
+    }
+
+    /// Close the connection
+    pub async fn close(self) {
+        drop(self.commands);
+        if let Some(task) = self.task {
+            let _ = task.await;
+        }
+    }
+
+    async fn request(&self, channel: &str, event: &str, payload: Value) -> McpResult<Value> {
+        let (reply, response) = oneshot::channel();
+        self.push(channel, event, payload, Some(reply))?;
+
+        match tokio::time::timeout(REPLY_TIMEOUT, response).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(McpError::RealtimeError("Connection closed".to_string())),
+            Err(_) => Err(McpError::RealtimeError(format!(
+                "No reply to {} on {}",
+                event, channel
+            ))),
+        }
+    }
+
+    fn push(
+        &self,
+        channel: &str,
+        event: &str,
+        payload: Value,
+        reply: Option<oneshot::Sender<McpResult<Value>>>,
+    ) -> McpResult<()> {
+        self.send(Command::Push {
+            topic: format!("realtime:{}", channel),
+            event: event.to_string(),
+            payload,
+            reply,
+        })
+    }
+
+    fn send(&self, command: Command) -> McpResult<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| McpError::RealtimeError("Connection closed".to_string()))
+    }
+}
+
+/// Own the socket: send commands and heartbeats, route replies and forward events
+async fn run_connection<S>(
+    socket: S,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    events: mpsc::UnboundedSender<RealtimeEvent>,
+) -> McpResult<()>
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+        + futures::Sink<Message, Error = tokio_tungstenite::tungstenite::Error>
+        + Unpin,
+{
+    let (mut sink, mut stream) = socket.split();
+
+    let mut next_ref: u64 = 0;
+    // Join ref of every joined topic; Phoenix drops channel messages without the right one
+    let mut joined: HashMap<String, String> = HashMap::new();
+    // Topic of every join still waiting for its reply, by reference
+    let mut joining: HashMap<String, String> = HashMap::new();
+    let mut replies: HashMap<String, oneshot::Sender<McpResult<Value>>> = HashMap::new();
+    let mut pending_heartbeat: Option<String> = None;
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    heartbeat.tick().await;
+
+    loop {
+        let mut outgoing = Vec::new();
+
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Push { topic, event, payload, reply }) => {
+                    forget_abandoned(&mut replies, &mut joining);
+
+                    next_ref += 1;
+                    let reference = next_ref.to_string();
+
+                    let join_ref = if event == "phx_join" {
+                        joining.insert(reference.clone(), topic.clone());
+                        Some(reference.clone())
+                    } else if event == "phx_leave" {
+                        joined.remove(&topic)
+                    } else {
+                        joined.get(&topic).cloned()
+                    };
+                    if let Some(reply) = reply {
+                        replies.insert(reference.clone(), reply);
+                    }
+
+                    outgoing.push(PhoenixMessage {
+                        topic,
+                        event,
+                        payload,
+                        reference: Some(reference),
+                        join_ref,
+                    });
+                }
+                Some(Command::AccessToken(token)) => {
+                    for (topic, join_ref) in &joined {
+                        next_ref += 1;
+                        outgoing.push(PhoenixMessage {
+                            topic: topic.clone(),
+                            event: "access_token".to_string(),
+                            payload: json!({ "access_token": token }),
+                            reference: Some(next_ref.to_string()),
+                            join_ref: Some(join_ref.clone()),
+                        });
+                    }
+                }
+                None => {
+                    let _ = sink.close().await;
+                    return Ok(());
+                }
+            },
+            _ = heartbeat.tick() => {
+                forget_abandoned(&mut replies, &mut joining);
+
+                if pending_heartbeat.is_some() {
+                    return Err(McpError::RealtimeError(
+                        "Server stopped answering heartbeats".to_string(),
+                    ));
+                }
+
+                next_ref += 1;
+                pending_heartbeat = Some(next_ref.to_string());
+                outgoing.push(PhoenixMessage {
+                    topic: "phoenix".to_string(),
+                    event: "heartbeat".to_string(),
+                    payload: json!({}),
+                    reference: pending_heartbeat.clone(),
+                    join_ref: None,
+                });
+            },
+            message = stream.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(frame))) => {
+                        let reason = frame.map(|frame| frame.reason.to_string()).unwrap_or_default();
+                        return Err(McpError::RealtimeError(format!("Server closed the connection {}", reason)));
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => return Err(McpError::RealtimeError(err.to_string())),
+                    None => return Err(McpError::RealtimeError("Connection closed".to_string())),
+                };
+
+                let message: PhoenixMessage = match serde_json::from_str(&text) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                };
+
+                if message.event == "phx_reply" {
+                    if message.reference.is_some() && message.reference == pending_heartbeat {
+                        pending_heartbeat = None;
+                    } else if let Some(reference) = message.reference {
+                        let result = reply_result(message.payload);
+
+                        // A channel only counts as joined once the server accepts it
+                        if let Some(topic) = joining.remove(&reference)
+                            && result.is_ok()
+                        {
+                            joined.insert(topic, reference.clone());
+                        }
+                        if let Some(reply) = replies.remove(&reference) {
+                            let _ = reply.send(result);
+                        }
+                    }
+                    continue;
+                }
+
+                if matches!(message.event.as_str(), "phx_close" | "phx_error") {
+                    joined.remove(&message.topic);
+                }
+                if let Some(event) = to_event(message) {
+                    // Nobody is listening any more; the client is shutting down
+                    let _ = events.send(event);
+                }
+            },
+        }
+
+        for message in outgoing {
+            let text = serde_json::to_string(&message)?;
+            sink.send(Message::text(text))
+                .await
+                .map_err(|e| McpError::RealtimeError(e.to_string()))?;
+        }
+    }
+}
+
+/// Drop replies whose requester gave up waiting, and the joins they were for
+fn forget_abandoned(
+    replies: &mut HashMap<String, oneshot::Sender<McpResult<Value>>>,
+    joining: &mut HashMap<String, String>,
+) {
+    replies.retain(|_, reply| !reply.is_closed());
+    joining.retain(|reference, _| replies.contains_key(reference));
+}
+
+/// The response of a `phx_reply`, or the reason the server refused the request
+fn reply_result(payload: Value) -> McpResult<Value> {
+    if payload["status"] == "ok" {
+        return Ok(payload["response"].clone());
+    }
+
+    let reason = payload["response"]["reason"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| payload["response"].to_string());
+    Err(McpError::RealtimeError(reason))
+}
+
+/// Turn a pushed message into an event; unknown events are dropped
+fn to_event(message: PhoenixMessage) -> Option<RealtimeEvent> {
+    let channel = message
+        .topic
+        .strip_prefix("realtime:")
+        .unwrap_or(&message.topic)
+        .to_string();
+    let payload = message.payload;
+
+    let event = match message.event.as_str() {
+        "postgres_changes" => RealtimeEvent::PostgresChanges {
+            channel,
+            change: serde_json::from_value(payload["data"].clone()).ok()?,
+        },
+        "broadcast" => RealtimeEvent::Broadcast {
+            channel,
+            event: payload["event"].as_str().unwrap_or_default().to_string(),
+            payload: payload["payload"].clone(),
+        },
+        "presence_state" => RealtimeEvent::PresenceState {
+            channel,
+            state: payload,
+        },
+        "presence_diff" => RealtimeEvent::PresenceDiff {
+            channel,
+            joins: payload["joins"].clone(),
+            leaves: payload["leaves"].clone(),
+        },
+        "system" => RealtimeEvent::System { channel, payload },
+        "phx_close" => RealtimeEvent::ChannelClosed {
+            channel,
+            reason: "closed by the server".to_string(),
+        },
+        "phx_error" => RealtimeEvent::ChannelClosed {
+            channel,
+            reason: "channel crashed on the server".to_string(),
+        },
+        _ => return None,
+    };
+
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::DuplexStream;
+    use tokio_tungstenite::WebSocketStream;
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    /// A connection task talking to a socket the test plays the server on
+    async fn connection() -> (
+        mpsc::UnboundedSender<Command>,
+        WebSocketStream<DuplexStream>,
+    ) {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let server = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (events, _) = mpsc::unbounded_channel();
+        tokio::spawn(run_connection(client, command_rx, events));
+
+        (commands, server)
+    }
+
+    fn join(topic: &str) -> (Command, oneshot::Receiver<McpResult<Value>>) {
+        let (reply, response) = oneshot::channel();
+        let command = Command::Push {
+            topic: topic.to_string(),
+            event: "phx_join".to_string(),
+            payload: json!({}),
+            reply: Some(reply),
+        };
+        (command, response)
+    }
+
+    async fn receive(server: &mut WebSocketStream<DuplexStream>) -> PhoenixMessage {
+        loop {
+            if let Message::Text(text) = server.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    async fn reply(server: &mut WebSocketStream<DuplexStream>, to: &PhoenixMessage, status: &str) {
+        let reply = PhoenixMessage {
+            topic: to.topic.clone(),
+            event: "phx_reply".to_string(),
+            payload: json!({ "status": status, "response": { "reason": "denied" } }),
+            reference: to.reference.clone(),
+            join_ref: to.join_ref.clone(),
+        };
+        let text = serde_json::to_string(&reply).unwrap();
+        server.send(Message::text(text)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn channels_are_joined_only_once_accepted() {
+        let (commands, mut server) = connection().await;
+
+        let (command, refused) = join("realtime:refused");
+        commands.send(command).unwrap();
+        let message = receive(&mut server).await;
+        reply(&mut server, &message, "error").await;
+        assert_eq!(
+            refused.await.unwrap().unwrap_err().to_string(),
+            "Realtime error: denied"
+        );
+
+        let (command, accepted) = join("realtime:accepted");
+        commands.send(command).unwrap();
+        let message = receive(&mut server).await;
+        reply(&mut server, &message, "ok").await;
+        accepted.await.unwrap().unwrap();
+
+        // Only the joined channel is sent the new token
+        commands
+            .send(Command::AccessToken("fresh".to_string()))
+            .unwrap();
+        let message = receive(&mut server).await;
+        assert_eq!(message.event, "access_token");
+        assert_eq!(message.topic, "realtime:accepted");
+
+        let (command, _) = join("realtime:probe");
+        commands.send(command).unwrap();
+        assert_eq!(receive(&mut server).await.topic, "realtime:probe");
+    }
+
+    #[tokio::test]
+    async fn abandoned_joins_are_forgotten() {
+        let (commands, mut server) = connection().await;
+
+        // The requester stops waiting before the server answers
+        let (command, response) = join("realtime:slow");
+        commands.send(command).unwrap();
+        let slow = receive(&mut server).await;
+        drop(response);
+
+        // The next push clears the abandoned join, so its late acceptance is ignored
+        let (command, accepted) = join("realtime:probe");
+        commands.send(command).unwrap();
+        let probe = receive(&mut server).await;
+        reply(&mut server, &slow, "ok").await;
+        reply(&mut server, &probe, "ok").await;
+        accepted.await.unwrap().unwrap();
+
+        commands
+            .send(Command::AccessToken("fresh".to_string()))
+            .unwrap();
+        assert_eq!(receive(&mut server).await.topic, "realtime:probe");
+
+        let (command, _) = join("realtime:after");
+        commands.send(command).unwrap();
+        assert_eq!(receive(&mut server).await.topic, "realtime:after");
+    }
+
+    #[test]
+    fn forgets_only_abandoned_replies() {
+        let mut replies = HashMap::new();
+        let mut joining = HashMap::new();
+
+        let (waiting, _response) = oneshot::channel();
+        replies.insert("1".to_string(), waiting);
+        joining.insert("1".to_string(), "realtime:kept".to_string());
+
+        let (abandoned, response) = oneshot::channel();
+        drop(response);
+        replies.insert("2".to_string(), abandoned);
+        joining.insert("2".to_string(), "realtime:dropped".to_string());
+
+        forget_abandoned(&mut replies, &mut joining);
+        assert_eq!(replies.keys().collect::<Vec<_>>(), ["1"]);
+        assert_eq!(joining.keys().collect::<Vec<_>>(), ["1"]);
+    }
+}
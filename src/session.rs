@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A Supabase auth session as returned by the token endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+
+    /// Unix timestamp (seconds) at which the access token expires
+    #[serde(default)]
+    pub expires_at: u64,
+}
+
+impl Session {
+    /// Wrap a bare access token that cannot be refreshed
+    pub fn from_access_token(access_token: String) -> Self {
+        Self {
+            access_token,
+            refresh_token: String::new(),
+            token_type: "bearer".to_string(),
+            expires_in: 0,
+            expires_at: u64::MAX,
+        }
+    }
+
+    /// Fill in `expires_at` from `expires_in` when the server did not send it
+    pub fn with_expiry(mut self) -> Self {
+        if self.expires_at == 0 {
+            self.expires_at = now_secs() + self.expires_in;
+        }
+        self
+    }
+
+    /// Whether the access token has expired or will within `leeway`
+    pub fn expires_within(&self, leeway: Duration) -> bool {
+        now_secs() + leeway.as_secs() >= self.expires_at
+    }
+}
+
+/// Current Unix time in seconds
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_is_derived_from_expires_in_when_missing() {
+        let before = now_secs();
+        let session: Session = serde_json::from_str(
+            r#"{"access_token":"a","refresh_token":"r","token_type":"bearer","expires_in":3600}"#,
+        )
+        .unwrap();
+        let session = session.with_expiry();
+        assert!((before + 3600..=now_secs() + 3600).contains(&session.expires_at));
+
+        // A stored session keeps the expiry it was saved with
+        let stored = Session {
+            expires_at: 42,
+            ..session
+        };
+        assert_eq!(stored.with_expiry().expires_at, 42);
+    }
+
+    #[test]
+    fn expires_within_counts_the_leeway() {
+        let mut session = Session::from_access_token("a".to_string());
+        assert!(!session.expires_within(Duration::from_secs(3600)));
+
+        session.expires_at = now_secs() + 60;
+        assert!(!session.expires_within(Duration::from_secs(10)));
+        assert!(session.expires_within(Duration::from_secs(120)));
+    }
+}
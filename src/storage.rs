@@ -0,0 +1,704 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures::StreamExt;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::{Body, Method, RequestBuilder, Response, StatusCode, header};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fmt;
+use std::future::Future;
+use std::io::{Cursor, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use url::Url;
+
+use crate::error::{McpError, McpResult};
+use crate::supabase::SupabaseClient;
+
+/// URI scheme of Storage paths on the command line
+const SCHEME: &str = "supabase";
+/// Files this large or larger are uploaded with the resumable (TUS) protocol
+const RESUMABLE_THRESHOLD: u64 = 6 * 1024 * 1024;
+/// Supabase requires every chunk of a resumable upload but the last to be exactly 6 MiB
+const RESUMABLE_CHUNK_SIZE: u64 = 6 * 1024 * 1024;
+const TUS_VERSION: &str = "1.0.0";
+/// Times a failed chunk is retried after asking the server how much it already has
+const RESUME_ATTEMPTS: u32 = 3;
+/// A transfer fails once no data has moved either way for this long
+#[cfg(not(test))]
+const TRANSFER_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+#[cfg(test)]
+const TRANSFER_IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Characters left unescaped in a path segment
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// An object in a bucket, written `supabase://bucket/path` on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectPath {
+    pub bucket: String,
+    /// Path within the bucket, without a leading slash; empty for the bucket root
+    pub path: String,
+}
+
+impl ObjectPath {
+    /// Parse `supabase://bucket/path`, or return `None` for anything else, such as a local path
+    pub fn parse(text: &str) -> McpResult<Option<Self>> {
+        let Some(rest) = text.strip_prefix(&format!("{}://", SCHEME)) else {
+            return Ok(None);
+        };
+
+        let (bucket, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(McpError::InvalidArguments(format!(
+                "{} has no bucket; expected {}://bucket/path",
+                text, SCHEME
+            )));
+        }
+
+        Ok(Some(Self {
+            bucket: bucket.to_string(),
+            path: path.trim_start_matches('/').to_string(),
+        }))
+    }
+
+    /// Whether the path names a folder rather than an object
+    pub fn is_folder(&self) -> bool {
+        self.path.is_empty() || self.path.ends_with('/')
+    }
+
+    /// The object's file name
+    pub fn file_name(&self) -> Option<&str> {
+        self.path.rsplit('/').next().filter(|name| !name.is_empty())
+    }
+
+    /// `bucket/path` with every segment escaped, for use in API URLs
+    fn encoded(&self) -> String {
+        std::iter::once(self.bucket.as_str())
+            .chain(self.path.split('/').filter(|segment| !segment.is_empty()))
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl fmt::Display for ObjectPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}/{}", SCHEME, self.bucket, self.path)
+    }
+}
+
+/// A Storage bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bucket {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub public: bool,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// An object or folder returned by a listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectInfo {
+    pub name: String,
+    /// `None` for folders
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+impl ObjectInfo {
+    pub fn is_folder(&self) -> bool {
+        self.id.is_none()
+    }
+
+    /// Size in bytes, when Storage reports it
+    pub fn size(&self) -> Option<u64> {
+        self.metadata.as_ref()?.get("size")?.as_u64()
+    }
+}
+
+/// Error body the Storage API returns with failed requests
+#[derive(Deserialize)]
+struct StorageErrorBody {
+    #[serde(default)]
+    error: Option<String>,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct SignedUrl {
+    #[serde(rename = "signedURL")]
+    signed_url: String,
+}
+
+impl SupabaseClient {
+    fn storage_request(&self, method: Method, path: &str, token: &str) -> RequestBuilder {
+        self.api_request(
+            method,
+            &self.api_url(&format!("storage/v1/{}", path)),
+            token,
+        )
+    }
+
+    /// Buckets the owner of `token` can see
+    pub async fn list_buckets(&self, token: &str) -> McpResult<Vec<Bucket>> {
+        let response = self
+            .storage_request(Method::GET, "bucket", token)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        check_response(response)
+            .await?
+            .json()
+            .await
+            .map_err(McpError::HttpError)
+    }
+
+    /// One page of the objects and folders directly under `prefix`
+    pub async fn list_objects(
+        &self,
+        token: &str,
+        bucket: &str,
+        prefix: &str,
+        limit: usize,
+        offset: usize,
+    ) -> McpResult<Vec<ObjectInfo>> {
+        let path = format!("object/list/{}", utf8_percent_encode(bucket, SEGMENT));
+        let response = self
+            .storage_request(Method::POST, &path, token)
+            .json(&json!({
+                "prefix": prefix.trim_matches('/'),
+                "limit": limit,
+                "offset": offset,
+                "sortBy": { "column": "name", "order": "asc" },
+            }))
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        check_response(response)
+            .await?
+            .json()
+            .await
+            .map_err(McpError::HttpError)
+    }
+
+    /// Upload a local file, streaming it; large files use a resumable upload
+    ///
+    /// Returns the number of bytes uploaded.
+    pub async fn upload_object(
+        &self,
+        token: &str,
+        object: &ObjectPath,
+        source: &Path,
+        upsert: bool,
+    ) -> McpResult<u64> {
+        let file = File::open(source).await?;
+        let size = file.metadata().await?.len();
+        let content_type = mime_guess::from_path(source)
+            .first_or_octet_stream()
+            .to_string();
+
+        if size >= RESUMABLE_THRESHOLD {
+            self.upload_resumable(token, object, file, size, &content_type, upsert)
+                .await?;
+            return Ok(size);
+        }
+
+        let progress = Progress::new();
+        let url = self.api_url(&format!("storage/v1/object/{}", object.encoded()));
+        let request = self
+            .transfer_request(Method::POST, &url, token)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, size)
+            .header("x-upsert", upsert.to_string())
+            .body(progress.body(file))
+            .send();
+        let response = progress
+            .unless_stalled(request)
+            .await?
+            .map_err(McpError::HttpError)?;
+
+        check_response(response).await?;
+        Ok(size)
+    }
+
+    /// Upload with the TUS protocol, resuming from the server's offset when a chunk fails
+    async fn upload_resumable(
+        &self,
+        token: &str,
+        object: &ObjectPath,
+        mut file: File,
+        size: u64,
+        content_type: &str,
+        upsert: bool,
+    ) -> McpResult<()> {
+        let metadata = [
+            ("bucketName", object.bucket.as_str()),
+            ("objectName", object.path.as_str()),
+            ("contentType", content_type),
+        ]
+        .iter()
+        .map(|(key, value)| format!("{} {}", key, STANDARD.encode(value)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+        let endpoint = self.api_url("storage/v1/upload/resumable");
+        let response = self
+            .api_request(Method::POST, &endpoint, token)
+            .header("Tus-Resumable", TUS_VERSION)
+            .header("Upload-Length", size)
+            .header("Upload-Metadata", metadata)
+            .header("x-upsert", upsert.to_string())
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        let response = check_response(response).await?;
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                McpError::StorageError("Resumable upload has no Location".to_string())
+            })?;
+        let upload_url = Url::parse(&endpoint)
+            .and_then(|endpoint| endpoint.join(location))
+            .map_err(|e| McpError::StorageError(format!("Invalid upload location: {}", e)))?
+            .to_string();
+
+        let mut offset = 0;
+        let mut attempts = 0;
+        while offset < size {
+            let length = RESUMABLE_CHUNK_SIZE.min(size - offset);
+            let mut chunk = vec![0; length as usize];
+            file.seek(SeekFrom::Start(offset)).await?;
+            file.read_exact(&mut chunk).await?;
+
+            let progress = Progress::new();
+            let request = self
+                .transfer_request(Method::PATCH, &upload_url, token)
+                .header("Tus-Resumable", TUS_VERSION)
+                .header("Upload-Offset", offset)
+                .header(header::CONTENT_TYPE, "application/offset+octet-stream")
+                .header(header::CONTENT_LENGTH, length)
+                .body(progress.body(Cursor::new(chunk)))
+                .send();
+            let result = match progress.unless_stalled(request).await {
+                Ok(result) => result.map_err(McpError::HttpError),
+                Err(stalled) => Err(stalled),
+            };
+
+            match result {
+                Ok(response) if response.status().is_success() => {
+                    offset = upload_offset(&response).unwrap_or(offset + length);
+                    attempts = 0;
+                }
+                // Rejections other than a conflicting offset or a server fault will not go away
+                Ok(response)
+                    if response.status() != StatusCode::CONFLICT
+                        && !response.status().is_server_error() =>
+                {
+                    check_response(response).await?;
+                }
+                failure => {
+                    attempts += 1;
+                    if attempts > RESUME_ATTEMPTS {
+                        return match failure {
+                            Ok(response) => check_response(response).await.map(|_| ()),
+                            Err(err) => Err(err),
+                        };
+                    }
+
+                    tokio::time::sleep(Duration::from_secs(attempts.into())).await;
+                    offset = self.resumable_offset(token, &upload_url).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How many bytes of a resumable upload the server has received
+    async fn resumable_offset(&self, token: &str, upload_url: &str) -> McpResult<u64> {
+        let response = self
+            .api_request(Method::HEAD, upload_url, token)
+            .header("Tus-Resumable", TUS_VERSION)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        upload_offset(&check_response(response).await?).ok_or_else(|| {
+            McpError::StorageError("Server did not report the upload offset".to_string())
+        })
+    }
+
+    /// Download an object into a local file, streaming it
+    ///
+    /// Returns the number of bytes written. A partial file is removed if the download fails.
+    pub async fn download_object(
+        &self,
+        token: &str,
+        object: &ObjectPath,
+        destination: &Path,
+    ) -> McpResult<u64> {
+        let progress = Progress::new();
+        let url = self.api_url(&format!(
+            "storage/v1/object/authenticated/{}",
+            object.encoded()
+        ));
+        let request = self.transfer_request(Method::GET, &url, token).send();
+        let response = progress
+            .unless_stalled(request)
+            .await?
+            .map_err(McpError::HttpError)?;
+        let mut response = check_response(response).await?;
+
+        let mut file = File::create(destination).await?;
+        let result = async {
+            let mut written = 0;
+            while let Some(chunk) = progress
+                .unless_stalled(response.chunk())
+                .await?
+                .map_err(McpError::HttpError)?
+            {
+                progress.touch();
+                file.write_all(&chunk).await?;
+                written += chunk.len() as u64;
+            }
+            file.flush().await?;
+            Ok(written)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(destination).await;
+        }
+        result
+    }
+
+    /// Move an object, possibly to another bucket
+    pub async fn move_object(
+        &self,
+        token: &str,
+        from: &ObjectPath,
+        to: &ObjectPath,
+    ) -> McpResult<()> {
+        self.relocate_object("object/move", token, from, to).await
+    }
+
+    /// Copy an object, possibly to another bucket
+    pub async fn copy_object(
+        &self,
+        token: &str,
+        from: &ObjectPath,
+        to: &ObjectPath,
+    ) -> McpResult<()> {
+        self.relocate_object("object/copy", token, from, to).await
+    }
+
+    async fn relocate_object(
+        &self,
+        endpoint: &str,
+        token: &str,
+        from: &ObjectPath,
+        to: &ObjectPath,
+    ) -> McpResult<()> {
+        let response = self
+            .storage_request(Method::POST, endpoint, token)
+            .json(&json!({
+                "bucketId": from.bucket,
+                "sourceKey": from.path,
+                "destinationBucket": to.bucket,
+                "destinationKey": to.path,
+            }))
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        check_response(response).await?;
+        Ok(())
+    }
+
+    /// Delete objects from a bucket, returning the ones that existed
+    pub async fn remove_objects(
+        &self,
+        token: &str,
+        bucket: &str,
+        paths: &[String],
+    ) -> McpResult<Vec<ObjectInfo>> {
+        let path = format!("object/{}", utf8_percent_encode(bucket, SEGMENT));
+        let response = self
+            .storage_request(Method::DELETE, &path, token)
+            .json(&json!({ "prefixes": paths }))
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        check_response(response)
+            .await?
+            .json()
+            .await
+            .map_err(McpError::HttpError)
+    }
+
+    /// Create a URL that grants anyone access to an object for `expires_in` seconds
+    pub async fn create_signed_url(
+        &self,
+        token: &str,
+        object: &ObjectPath,
+        expires_in: u64,
+    ) -> McpResult<String> {
+        let response = self
+            .storage_request(
+                Method::POST,
+                &format!("object/sign/{}", object.encoded()),
+                token,
+            )
+            .json(&json!({ "expiresIn": expires_in }))
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        let signed: SignedUrl = check_response(response)
+            .await?
+            .json()
+            .await
+            .map_err(McpError::HttpError)?;
+
+        // The API answers with a path relative to the Storage endpoint
+        Ok(format!(
+            "{}{}",
+            self.api_url("storage/v1"),
+            signed.signed_url
+        ))
+    }
+}
+
+/// When a transfer last moved data, so that stalled transfers fail but slow ones do not
+#[derive(Clone)]
+struct Progress(Arc<Mutex<Instant>>);
+
+impl Progress {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    fn touch(&self) {
+        *self.0.lock().unwrap() = Instant::now();
+    }
+
+    /// A request body streamed from `reader`, counting every chunk the server takes
+    fn body(&self, reader: impl AsyncRead + Send + Sync + 'static) -> Body {
+        let progress = self.clone();
+        Body::wrap_stream(ReaderStream::new(reader).inspect(move |_| progress.touch()))
+    }
+
+    /// Run `work`, giving up once it has gone `TRANSFER_IDLE_TIMEOUT` without progress
+    async fn unless_stalled<T>(&self, work: impl Future<Output = T>) -> McpResult<T> {
+        tokio::select! {
+            result = work => Ok(result),
+            _ = self.stalled() => Err(McpError::StorageError(format!(
+                "Transfer stalled: no data moved for {} seconds",
+                TRANSFER_IDLE_TIMEOUT.as_secs_f32()
+            ))),
+        }
+    }
+
+    async fn stalled(&self) {
+        loop {
+            let idle = self.0.lock().unwrap().elapsed();
+            if idle >= TRANSFER_IDLE_TIMEOUT {
+                return;
+            }
+            tokio::time::sleep(TRANSFER_IDLE_TIMEOUT - idle).await;
+        }
+    }
+}
+
+/// Local file to download `object` to, given the `cp` destination
+pub fn local_destination(object: &ObjectPath, destination: &Path) -> McpResult<PathBuf> {
+    if !destination.is_dir() {
+        return Ok(destination.to_path_buf());
+    }
+
+    let name = object.file_name().ok_or_else(|| {
+        McpError::InvalidArguments(format!("{} is a folder, not an object", object))
+    })?;
+    Ok(destination.join(name))
+}
+
+/// Object to upload `source` to, given the `cp` destination
+pub fn remote_destination(source: &Path, destination: &ObjectPath) -> McpResult<ObjectPath> {
+    if !destination.is_folder() {
+        return Ok(destination.clone());
+    }
+
+    let name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            McpError::InvalidArguments(format!("{} has no file name", source.display()))
+        })?;
+    Ok(ObjectPath {
+        bucket: destination.bucket.clone(),
+        path: format!("{}{}", destination.path, name),
+    })
+}
+
+fn upload_offset(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get("Upload-Offset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Turn a failed Storage response into a `StorageError` with the server's explanation
+async fn check_response(response: Response) -> McpResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<StorageErrorBody>(&body) {
+        Ok(StorageErrorBody {
+            error: Some(error),
+            message,
+        }) if error != message => {
+            format!("{} ({})", message, error)
+        }
+        Ok(StorageErrorBody { message, .. }) => message,
+        Err(_) => format!("HTTP {}", status),
+    };
+
+    Err(McpError::StorageError(message))
+}
+
+#[cfg(test)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::supabase::REQUEST_TIMEOUT;
+    use tokio::net::TcpListener;
+
+    fn parse(text: &str) -> ObjectPath {
+        ObjectPath::parse(text).unwrap().unwrap()
+    }
+
+    #[test]
+    fn parses_object_paths() {
+        let object = parse("supabase://avatars//users/me.png");
+        assert_eq!(
+            (object.bucket.as_str(), object.path.as_str()),
+            ("avatars", "users/me.png")
+        );
+        assert!(parse("supabase://avatars").is_folder());
+        assert_eq!(ObjectPath::parse("./avatars/me.png").unwrap(), None);
+        assert!(ObjectPath::parse("supabase:///me.png").is_err());
+    }
+
+    #[test]
+    fn escapes_each_segment_for_api_urls() {
+        let object = parse("supabase://my bucket/users//a b#1.png");
+        assert_eq!(object.file_name(), Some("a b#1.png"));
+        assert_eq!(object.encoded(), "my%20bucket/users/a%20b%231.png");
+        assert_eq!(parse("supabase://avatars/users/").file_name(), None);
+    }
+
+    #[test]
+    fn copies_into_folders_keep_the_file_name() {
+        let source = Path::new("/tmp/report.pdf");
+        let into_folder = remote_destination(source, &parse("supabase://docs/2024/")).unwrap();
+        assert_eq!(into_folder, parse("supabase://docs/2024/report.pdf"));
+        let renamed = remote_destination(source, &parse("supabase://docs/renamed.pdf")).unwrap();
+        assert_eq!(renamed, parse("supabase://docs/renamed.pdf"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let object = parse("supabase://docs/2024/report.pdf");
+        assert_eq!(
+            local_destination(&object, dir.path()).unwrap(),
+            dir.path().join("report.pdf")
+        );
+        assert!(local_destination(&parse("supabase://docs/2024/"), dir.path()).is_err());
+    }
+
+    /// Serve one download of `pieces`, each after `gap`, then hold the connection open
+    async fn trickling_project(pieces: usize, gap: Duration) -> SupabaseClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                socket.read_exact(&mut byte).await.unwrap();
+                request.push(byte[0]);
+            }
+
+            let head = "HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n";
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for _ in 0..pieces {
+                tokio::time::sleep(gap).await;
+                socket.write_all(b"xx").await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        SupabaseClient::new(Config {
+            supabase_url: url,
+            ..Config::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn downloads_may_outlast_the_request_timeout() {
+        let gap = TRANSFER_IDLE_TIMEOUT / 2;
+        assert!(gap * 5 > REQUEST_TIMEOUT);
+        let supabase = trickling_project(5, gap).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("slow.bin");
+        let object = parse("supabase://docs/slow.bin");
+        let written = supabase
+            .download_object("token", &object, &destination)
+            .await;
+
+        assert_eq!(written.unwrap(), 10);
+        assert_eq!(std::fs::read(&destination).unwrap(), b"xxxxxxxxxx");
+    }
+
+    #[tokio::test]
+    async fn stalled_downloads_fail_and_leave_no_file() {
+        let supabase = trickling_project(1, Duration::ZERO).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("stalled.bin");
+        let object = parse("supabase://docs/stalled.bin");
+        let result = supabase
+            .download_object("token", &object, &destination)
+            .await;
+
+        assert!(matches!(result, Err(McpError::StorageError(_))));
+        assert!(!destination.exists());
+    }
+}
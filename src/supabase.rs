@@ -1,71 +1,234 @@
-use reqwest::{Client, header};
+use base64::Engine;
+use clap::ValueEnum;
+use jsonwebtoken::jwk::JwkSet;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, header};
 use serde::{Deserialize, Serialize};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
+use tokio::sync::OnceCell;
 use url::Url;
 
 use crate::config::Config;
+use crate::discovery::AuthorizationServerMetadata;
 use crate::error::{McpError, McpResult, to_auth_error};
+use crate::pkce::CODE_CHALLENGE_METHOD;
+use crate::provider::OAuthProvider;
+use crate::session::Session;
+
+/// Number of random bytes in a generated OAuth state value
+const STATE_BYTES: usize = 32;
+
+/// Time limit on whole API requests, which are expected to answer quickly
+#[cfg(not(test))]
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+#[cfg(test)]
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time limit on connecting for transfers, which otherwise take as long as they need
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error codes Supabase Auth gives a refresh token that can never be used again
+const DEAD_REFRESH_TOKEN_CODES: &[&str] = &[
+    "refresh_token_not_found",
+    "refresh_token_already_used",
+    "session_not_found",
+    "session_expired",
+    "user_not_found",
+];
 
 /// Supabase client for interacting with Supabase APIs
 pub struct SupabaseClient {
     client: Client,
+    /// Client without an overall timeout, for uploads, downloads and streamed responses
+    transfer_client: Client,
     config: Config,
+    
+    /// Endpoints of the project's auth server, discovered on first use
+    metadata: OnceCell<AuthorizationServerMetadata>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserProfile {
-    id: String,
-    email: Option<String>,
-    user_metadata: UserMetadata,
-    app_metadata: AppMetadata,
-    created_at: String,
+    pub id: String,
+    pub email: Option<String>,
+    pub user_metadata: UserMetadata,
+    pub app_metadata: AppMetadata,
+    pub created_at: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserMetadata {
-    avatar_url: Option<String>,
-    email: Option<String>,
-    email_verified: Option<bool>,
-    full_name: Option<String>,
-    iss: Option<String>,
-    name: Option<String>,
-    preferred_username: Option<String>,
-    provider_id: Option<String>,
-    sub: Option<String>,
-    user_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub full_name: Option<String>,
+    pub iss: Option<String>,
+    pub name: Option<String>,
+    pub preferred_username: Option<String>,
+    pub provider_id: Option<String>,
+    pub sub: Option<String>,
+    pub user_name: Option<String>,
+}
+
+/// A sign-in identity linked to a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    /// ID of the identity itself, used to unlink it
+    pub identity_id: String,
+    /// ID of the user at the provider
+    pub id: String,
+    pub provider: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub identity_data: serde_json::Value,
+    #[serde(default)]
+    pub last_sign_in_at: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppMetadata {
-    provider: String,
-    providers: Vec<String>,
+    pub provider: String,
+    pub providers: Vec<String>,
+}
+
+/// Which sessions a logout revokes
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LogoutScope {
+    /// Every session of the user, on all devices
+    Global,
+    /// Only the current session
+    Local,
+    /// All sessions except the current one
+    Others,
+}
+
+impl LogoutScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogoutScope::Global => "global",
+            LogoutScope::Local => "local",
+            LogoutScope::Others => "others",
+        }
+    }
 }
 
 impl SupabaseClient {
     /// Create a new Supabase client
     pub fn new(config: Config) -> Self {
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to create HTTP client");
+        let transfer_client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
             .build()
             .expect("Failed to create HTTP client");
         
-        Self { client, config }
+        Self {
+            client,
+            transfer_client,
+            config,
+            metadata: OnceCell::new(),
+        }
+    }
+    
+    /// Metadata of the project's auth server
+    ///
+    /// Discovered from the well-known metadata documents; projects that publish none, or
+    /// whose metadata cannot be fetched, get the standard Supabase Auth endpoints.
+    pub async fn authorization_server(&self) -> &AuthorizationServerMetadata {
+        self.metadata
+            .get_or_init(|| async {
+                let issuer = self.config.auth_issuer();
+                let discovered = AuthorizationServerMetadata::discover(
+                    &self.client,
+                    &issuer,
+                    &self.config.supabase_anon_key,
+                ).await;
+                
+                // The gateway may refuse the well-known paths or be briefly unavailable; the
+                // defaults are right for every Supabase project, so only a hint is lost
+                match discovered {
+                    Ok(Some(metadata)) => metadata,
+                    Ok(None) | Err(_) => AuthorizationServerMetadata::supabase_defaults(&issuer),
+                }
+            })
+            .await
+    }
+    
+    /// The project's anon key, which identifies the project to its APIs
+    pub(crate) fn anon_key(&self) -> &str {
+        &self.config.supabase_anon_key
+    }
+    
+    /// URL of `path` under the project's API gateway, e.g. `storage/v1/bucket`
+    pub(crate) fn api_url(&self, path: &str) -> String {
+        format!("{}/{}", self.config.supabase_url.trim_end_matches('/'), path)
+    }
+    
+    /// Start a request to one of the project's APIs as the owner of `token`
+    pub(crate) fn api_request(&self, method: Method, url: &str, token: &str) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .bearer_auth(token)
+            .header("apikey", &self.config.supabase_anon_key)
     }
     
-    /// Build the GitHub OAuth URL for signup or login
-    pub fn build_github_auth_url(&self, is_signup: bool) -> McpResult<String> {
-        let base_url = format!("{}/auth/v1/authorize", self.config.supabase_url);
+    /// Like [`Self::api_request`], but for transfers that may run longer than an API call
+    pub(crate) fn transfer_request(&self, method: Method, url: &str, token: &str) -> RequestBuilder {
+        self.transfer_client
+            .request(method, url)
+            .bearer_auth(token)
+            .header("apikey", &self.config.supabase_anon_key)
+    }
+    
+    /// Start a request to the project's REST API (PostgREST) as the owner of `token`
+    pub(crate) fn rest_request(&self, method: Method, path: &str, token: &str) -> RequestBuilder {
+        self.api_request(method, &self.api_url(&format!("rest/v1/{}", path)), token)
+    }
+    
+    /// Build the OAuth URL for signup or login with `provider`
+    ///
+    /// The URL starts a PKCE authorization-code flow that redirects back to `redirect_to`.
+    /// Supabase keeps its own state with the provider, so `state` is also carried on the
+    /// redirect URL itself, where the callback can be checked against it.
+    pub async fn build_auth_url(
+        &self,
+        provider: &OAuthProvider,
+        is_signup: bool,
+        redirect_to: &str,
+        code_challenge: &str,
+        state: &str,
+    ) -> McpResult<String> {
+        let metadata = self.authorization_server().await;
         
-        let mut url = Url::parse(&base_url).map_err(to_auth_error)?;
+        let mut url = Url::parse(&metadata.authorization_endpoint).map_err(to_auth_error)?;
+        
+        let mut redirect_url = Url::parse(redirect_to).map_err(to_auth_error)?;
+        redirect_url.query_pairs_mut().append_pair("state", state);
         
         // Add query parameters
         url.query_pairs_mut()
-            .append_pair("provider", "github")
-            .append_pair("client_id", &self.config.github_client_id)
-            .append_pair("redirect_to", &format!("{}/auth/v1/callback", self.config.supabase_url))
-            .append_pair("response_type", "token")
-            .append_pair("scopes", "user:email")
-            .append_pair("state", &generate_state());
+            .append_pair("provider", &provider.name)
+            .append_pair("redirect_to", redirect_url.as_str())
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", CODE_CHALLENGE_METHOD)
+            .append_pair("state", state);
+        
+        if provider.name == "github" {
+            url.query_pairs_mut().append_pair("client_id", &self.config.github_client_id);
+        }
+        
+        if let Some(scopes) = &provider.scopes {
+            url.query_pairs_mut().append_pair("scopes", scopes);
+        }
+        
+        for (key, value) in &provider.query_params {
+            url.query_pairs_mut().append_pair(key, value);
+        }
         
         if is_signup {
             url.query_pairs_mut().append_pair("flow_type", "signup");
@@ -74,14 +237,363 @@ impl SupabaseClient {
         Ok(url.to_string())
     }
     
-    /// Get the callback URL prefix for checking successful authentication
-    pub fn get_callback_url_prefix(&self) -> String {
-        format!("{}/auth/v1/callback", self.config.supabase_url)
+    /// Build the URL that links an identity from `provider` to the user owning `token`
+    ///
+    /// Like [`Self::build_auth_url`], it starts a PKCE flow that redirects to `redirect_to`
+    /// with `state` attached.
+    pub async fn build_link_identity_url(
+        &self,
+        token: &str,
+        provider: &OAuthProvider,
+        redirect_to: &str,
+        code_challenge: &str,
+        state: &str,
+    ) -> McpResult<String> {
+        let mut url = Url::parse(&format!("{}/user/identities/authorize", self.config.auth_issuer()))
+            .map_err(to_auth_error)?;
+        
+        let mut redirect_url = Url::parse(redirect_to).map_err(to_auth_error)?;
+        redirect_url.query_pairs_mut().append_pair("state", state);
+        
+        // Ask for the provider URL as JSON rather than as a redirect
+        url.query_pairs_mut()
+            .append_pair("provider", &provider.name)
+            .append_pair("redirect_to", redirect_url.as_str())
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", CODE_CHALLENGE_METHOD)
+            .append_pair("skip_http_redirect", "true");
+        
+        if let Some(scopes) = &provider.scopes {
+            url.query_pairs_mut().append_pair("scopes", scopes);
+        }
+        
+        for (key, value) in &provider.query_params {
+            url.query_pairs_mut().append_pair(key, value);
+        }
+        
+        let response = self
+            .api_request(Method::GET, url.as_str(), token)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Failed to start linking the identity").await);
+        }
+        
+        let body: serde_json::Value = response.json().await.map_err(McpError::HttpError)?;
+        body["url"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| McpError::AuthError("Auth server returned no authorization URL".to_string()))
+    }
+    
+    /// The identities linked to the user owning `token`
+    pub async fn list_identities(&self, token: &str) -> McpResult<Vec<Identity>> {
+        let response = self
+            .api_request(Method::GET, &format!("{}/user", self.config.auth_issuer()), token)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Failed to get identities").await);
+        }
+        
+        let mut user: serde_json::Value = response.json().await.map_err(McpError::HttpError)?;
+        let identities: Option<Vec<Identity>> = serde_json::from_value(user["identities"].take())?;
+        
+        Ok(identities.unwrap_or_default())
+    }
+    
+    /// Remove an identity from the user owning `token`
+    pub async fn unlink_identity(&self, token: &str, identity_id: &str) -> McpResult<()> {
+        let url = format!("{}/user/identities/{}", self.config.auth_issuer(), identity_id);
+        
+        let response = self
+            .api_request(Method::DELETE, &url, token)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Failed to unlink the identity").await);
+        }
+        
+        Ok(())
+    }
+    
+    /// Names of the external providers enabled on the project, from the Auth settings
+    pub async fn enabled_providers(&self) -> McpResult<Vec<String>> {
+        let response = self.client
+            .get(format!("{}/settings", self.config.auth_issuer()))
+            .header("apikey", &self.config.supabase_anon_key)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Failed to fetch Auth settings").await);
+        }
+        
+        let settings: serde_json::Value = response.json().await.map_err(McpError::HttpError)?;
+        let providers = settings["external"]
+            .as_object()
+            .map(|external| {
+                external
+                    .iter()
+                    .filter(|(_, enabled)| enabled.as_bool() == Some(true))
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        
+        Ok(providers)
+    }
+    
+    /// Exchange an authorization code and its PKCE verifier for a session
+    pub async fn exchange_code_for_session(
+        &self,
+        auth_code: &str,
+        code_verifier: &str,
+    ) -> McpResult<Session> {
+        let body = serde_json::json!({
+            "auth_code": auth_code,
+            "code_verifier": code_verifier,
+        });
+        
+        self.request_session("pkce", &body).await
+    }
+    
+    /// Exchange an identity provider's access token for a session
+    ///
+    /// Uses `grant_type` at the token endpoint, which Supabase Auth itself does not offer for
+    /// access tokens; the auth server must add it.
+    pub async fn exchange_provider_token(
+        &self,
+        grant_type: &str,
+        provider: &str,
+        token: &str,
+    ) -> McpResult<Session> {
+        let body = serde_json::json!({
+            "provider": provider,
+            "access_token": token,
+        });
+        
+        self.request_session(grant_type, &body).await
+    }
+    
+    /// Email a magic link and one-time code to an existing user
+    ///
+    /// The link starts a PKCE flow that redirects to `redirect_to`, carrying `state` like
+    /// [`Self::build_auth_url`]; the code can be passed to [`Self::verify_email_otp`].
+    pub async fn send_email_otp(
+        &self,
+        email: &str,
+        redirect_to: &str,
+        code_challenge: &str,
+        state: &str,
+    ) -> McpResult<()> {
+        let mut url = Url::parse(&format!("{}/otp", self.config.auth_issuer())).map_err(to_auth_error)?;
+        let mut redirect_url = Url::parse(redirect_to).map_err(to_auth_error)?;
+        redirect_url.query_pairs_mut().append_pair("state", state);
+        url.query_pairs_mut().append_pair("redirect_to", redirect_url.as_str());
+        
+        let body = serde_json::json!({
+            "email": email,
+            "create_user": false,
+            "code_challenge": code_challenge,
+            "code_challenge_method": CODE_CHALLENGE_METHOD,
+        });
+        
+        let response = self.client
+            .post(url)
+            .header("apikey", &self.config.supabase_anon_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Failed to send the sign-in email").await);
+        }
+        
+        Ok(())
+    }
+    
+    /// Trade the one-time code from a sign-in email for a session
+    pub async fn verify_email_otp(&self, email: &str, code: &str) -> McpResult<Session> {
+        let body = serde_json::json!({
+            "type": "email",
+            "email": email,
+            "token": code,
+        });
+        
+        let response = self.client
+            .post(format!("{}/verify", self.config.auth_issuer()))
+            .header("apikey", &self.config.supabase_anon_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Code verification failed").await);
+        }
+        
+        let session = response
+            .json::<Session>()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        Ok(session.with_expiry())
+    }
+    
+    /// Create a user with an email address and password
+    ///
+    /// Returns `None` when the project requires the address to be confirmed before the user
+    /// can sign in.
+    pub async fn sign_up_with_password(&self, email: &str, password: &str) -> McpResult<Option<Session>> {
+        let body = serde_json::json!({
+            "email": email,
+            "password": password,
+        });
+        
+        let response = self.client
+            .post(format!("{}/signup", self.config.auth_issuer()))
+            .header("apikey", &self.config.supabase_anon_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(auth_failure(response, "Signup failed").await);
+        }
+        
+        // Without a session the response is just the new, unconfirmed user
+        let body: serde_json::Value = response.json().await.map_err(McpError::HttpError)?;
+        if body.get("access_token").is_none() {
+            return Ok(None);
+        }
+        
+        let session: Session = serde_json::from_value(body)?;
+        Ok(Some(session.with_expiry()))
+    }
+    
+    /// Sign in with an email address and password
+    pub async fn sign_in_with_password(&self, email: &str, password: &str) -> McpResult<Session> {
+        let body = serde_json::json!({
+            "email": email,
+            "password": password,
+        });
+        
+        self.request_session("password", &body).await
+    }
+    
+    /// Trade a refresh token for a new session
+    ///
+    /// Supabase rotates refresh tokens, so the returned session must replace the stored one.
+    pub async fn refresh_session(&self, refresh_token: &str) -> McpResult<Session> {
+        let body = serde_json::json!({ "refresh_token": refresh_token });
+        
+        self.request_session("refresh_token", &body).await
+    }
+    
+    /// POST to the token endpoint with the given grant type
+    async fn request_session(
+        &self,
+        grant_type: &str,
+        body: &serde_json::Value,
+    ) -> McpResult<Session> {
+        let metadata = self.authorization_server().await;
+        
+        let mut url = Url::parse(&metadata.token_endpoint).map_err(to_auth_error)?;
+        url.query_pairs_mut().append_pair("grant_type", grant_type);
+        
+        let response = self.client
+            .post(url)
+            .header("apikey", &self.config.supabase_anon_key)
+            .json(body)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        let status = response.status();
+        if !status.is_success() {
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            
+            // Only a rejected refresh token ends the session; outages and rate limits do not
+            if grant_type == "refresh_token" && is_dead_refresh_token(status, &body) {
+                return Err(McpError::SessionExpired);
+            }
+            
+            let action = format!("Token request ({}) failed", grant_type);
+            return Err(auth_error(status, &body, &action));
+        }
+        
+        let session = response
+            .json::<Session>()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        Ok(session.with_expiry())
+    }
+    
+    /// Revoke the session server-side
+    pub async fn logout(&self, token: &str, scope: LogoutScope) -> McpResult<()> {
+        let url = format!("{}/logout?scope={}", self.config.auth_issuer(), scope.as_str());
+        
+        let response = self.client
+            .post(&url)
+            .bearer_auth(token)
+            .header("apikey", &self.config.supabase_anon_key)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(McpError::AuthError(format!(
+                "Failed to revoke session: HTTP {}",
+                response.status()
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    /// Fetch the project's JSON Web Key Set used to sign access tokens
+    pub async fn get_jwks(&self) -> McpResult<JwkSet> {
+        let metadata = self.authorization_server().await;
+        let url = metadata.jwks_uri.as_deref().ok_or_else(|| {
+            McpError::AuthError("Authorization server publishes no JWKS".to_string())
+        })?;
+        
+        let response = self.client
+            .get(url)
+            .header("apikey", &self.config.supabase_anon_key)
+            .send()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        if !response.status().is_success() {
+            return Err(McpError::AuthError(format!(
+                "Failed to fetch JWKS: HTTP {}",
+                response.status()
+            )));
+        }
+        
+        let jwks = response
+            .json::<JwkSet>()
+            .await
+            .map_err(McpError::HttpError)?;
+        
+        Ok(jwks)
     }
     
     /// Get the current user profile using the provided token
     pub async fn get_user_profile(&self, token: &str) -> McpResult<UserProfile> {
-        let url = format!("{}/auth/v1/user", self.config.supabase_url);
+        let url = format!("{}/user", self.config.auth_issuer());
         
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -118,14 +630,80 @@ impl SupabaseClient {
     }
 }
 
-/// Generate a random state string for the OAuth flow
-fn generate_state() -> String {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs();
+/// An auth error for a failed Auth API response, with the server's message when it sent one
+async fn auth_failure(response: Response, action: &str) -> McpError {
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    
+    auth_error(status, &body, action)
+}
+
+fn auth_error(status: StatusCode, body: &serde_json::Value, action: &str) -> McpError {
+    if body["error_code"] == "email_not_confirmed" {
+        return McpError::EmailNotConfirmed;
+    }
+    
+    // Supabase Auth uses `msg`; OAuth-style endpoints use `error_description`
+    let message = ["msg", "error_description", "message", "error"]
+        .iter()
+        .find_map(|key| body[key].as_str());
+    
+    match message {
+        Some(message) => McpError::AuthError(format!("{}: {}", action, message)),
+        None => McpError::AuthError(format!("{}: HTTP {}", action, status)),
+    }
+}
+
+/// Whether a failed refresh means the refresh token was rejected for good
+fn is_dead_refresh_token(status: StatusCode, body: &serde_json::Value) -> bool {
+    if status != StatusCode::BAD_REQUEST && status != StatusCode::UNAUTHORIZED {
+        return false;
+    }
+    
+    // Older Auth versions answer with an OAuth-style `invalid_grant`
+    body["error"] == "invalid_grant"
+        || body["error_code"]
+            .as_str()
+            .is_some_and(|code| DEAD_REFRESH_TOKEN_CODES.contains(&code))
+}
+
+/// Generate a cryptographically random, URL-safe state string for the OAuth flow
+pub fn generate_state() -> String {
+    let mut bytes = [0u8; STATE_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
     
-    let random = rand::random::<u64>();
+    #[test]
+    fn rejected_refresh_tokens_end_the_session() {
+        let not_found = json!({
+            "error_code": "refresh_token_not_found",
+            "msg": "Invalid Refresh Token: Refresh Token Not Found",
+        });
+        assert!(is_dead_refresh_token(StatusCode::BAD_REQUEST, &not_found));
+        
+        let legacy = json!({
+            "error": "invalid_grant",
+            "error_description": "Invalid Refresh Token: Refresh Token Not Found",
+        });
+        assert!(is_dead_refresh_token(StatusCode::BAD_REQUEST, &legacy));
+        assert!(is_dead_refresh_token(StatusCode::UNAUTHORIZED, &legacy));
+    }
     
-    format!("{}{}", timestamp, random)
+    #[test]
+    fn other_refresh_failures_are_passed_through() {
+        let limited = json!({ "error_code": "over_request_rate_limit", "msg": "Rate limit" });
+        assert!(!is_dead_refresh_token(StatusCode::TOO_MANY_REQUESTS, &limited));
+        assert!(!is_dead_refresh_token(StatusCode::BAD_GATEWAY, &json!({})));
+        assert!(!is_dead_refresh_token(StatusCode::BAD_REQUEST, &limited));
+        
+        let error = auth_error(StatusCode::TOO_MANY_REQUESTS, &limited, "Token request failed");
+        assert_eq!(error.to_string(), "Authentication error: Token request failed: Rate limit");
+    }
 }